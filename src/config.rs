@@ -0,0 +1,436 @@
+//! Defaults of the options from configuration files and the environment.
+//!
+//! Settings are named after the long command line flags, e.g. `width`,
+//! `format` or `max-entry-size`, and `output` and `rest` stand for the two
+//! values of `--output`. They are read, each overriding the ones before, from
+//!
+//! 1. the user configuration, `$XDG_CONFIG_HOME/rusty-html-extractor/config.toml`
+//!    or `~/.config/rusty-html-extractor/config.toml`,
+//! 2. the project configuration, the nearest `.rusty-html-extractor.toml` in
+//!    the current directory or one of its parents,
+//! 3. `RHE_*` environment variables, such as `RHE_WIDTH` or
+//!    `RHE_MAX_ENTRY_SIZE`.
+//!
+//! Flags given on the command line override all of them.
+//!
+//! The files may also define named profiles bundling settings, as tables
+//! below `profiles`:
+//!
+//! ```toml
+//! [profiles.review]
+//! format = "rich"
+//! width = 100
+//! artifacts = true
+//! ```
+//!
+//! A profile selected with `--profile` overrides the other settings of the
+//! files and the environment. A profile defined in both files is merged, the
+//! project's settings winning.
+
+use crate::error::Context;
+use crate::options::{FORMATS, LINK_STYLES, PRESETS, TABLE_STYLES, WHITESPACE_POLICIES};
+use crate::{MyError, Options};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// File name of the project configuration.
+pub const PROJECT_CONFIG: &str = ".rusty-html-extractor.toml";
+
+const ENV_PREFIX: &str = "RHE_";
+
+/// Every setting, in the order they are looked up in the environment.
+const SETTINGS: &[&str] = &[
+    "width",
+    "artifacts",
+    "format",
+    "output",
+    "rest",
+    "html",
+    "not-html",
+    "keep-temp",
+    "epub",
+    "recursive",
+    "max-depth",
+    "links",
+    "tables",
+    "whitespace",
+    "preset",
+    "header",
+    "footer",
+    "separator",
+    "split",
+    "input-encoding",
+    "main-content",
+    "select",
+    "exclude",
+    "include",
+    "copy",
+    "max-total-size",
+    "max-entry-size",
+    "max-entries",
+    "max-ratio",
+    "skip-over-limit",
+    "jobs",
+    "keep-going",
+];
+
+/// Settings collected from the configuration files and the environment.
+#[derive(Clone, Default, Debug)]
+pub struct Config {
+    /// Where every layer of settings comes from and its settings, lowest
+    /// precedence first.
+    layers: Vec<(String, Table)>,
+    /// Profiles of the files read, with the file they come from.
+    profiles: Vec<(String, String, Table)>,
+}
+
+impl Config {
+    /// Read the user and project configuration files and the environment.
+    pub fn load() -> Result<Self, MyError> {
+        let mut config = Self::default();
+        for path in user_config().into_iter().chain(project_config()) {
+            config.read(&path)?;
+        }
+        config.read_env();
+        Ok(config)
+    }
+
+    /// Add the settings of the `RHE_*` environment variables.
+    fn read_env(&mut self) {
+        for key in SETTINGS {
+            let name = env_name(key);
+            if let Ok(value) = std::env::var(&name) {
+                let mut layer = Table::new();
+                layer.insert(key.to_string(), Value::String(value));
+                self.layers.push((name, layer));
+            }
+        }
+    }
+
+    /// Add the settings of the TOML file at `path`, overriding the ones read
+    /// so far.
+    pub fn read(&mut self, path: &Path) -> Result<(), MyError> {
+        let text = std::fs::read_to_string(path).reading(path)?;
+        let invalid = |e: &dyn std::fmt::Display| {
+            MyError::Usage(format!("Invalid configuration {}: {}", path.display(), e))
+        };
+        let mut table = text.parse::<Table>().map_err(|e| invalid(&e))?;
+        let origin = path.display().to_string();
+        match table.remove("profiles") {
+            Some(Value::Table(profiles)) => {
+                for (name, profile) in profiles {
+                    match profile {
+                        Value::Table(profile) => {
+                            self.profiles.push((name, origin.clone(), profile))
+                        }
+                        _ => return Err(invalid(&format!("profile `{}` is not a table", name))),
+                    }
+                }
+            }
+            Some(_) => return Err(invalid(&"`profiles` is not a table")),
+            None => {}
+        }
+        self.layers.push((origin, table));
+        Ok(())
+    }
+
+    /// Apply the profile `name` over the settings read so far.
+    pub fn select(&mut self, name: &str) -> Result<(), MyError> {
+        let selected = self
+            .profiles
+            .iter()
+            .filter(|(profile, _, _)| profile == name)
+            .map(|(_, origin, table)| (format!("profile {} in {}", name, origin), table.clone()))
+            .collect::<Vec<_>>();
+        if selected.is_empty() {
+            let names = self.profile_names();
+            let known = match names.is_empty() {
+                true => "none are defined".to_string(),
+                false => format!("known are {}", names.join(", ")),
+            };
+            return Err(MyError::Usage(format!(
+                "Unknown profile `{}`, {}",
+                name, known
+            )));
+        }
+        self.layers.extend(selected);
+        Ok(())
+    }
+
+    /// Names of the profiles defined in the files read, sorted.
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names = self
+            .profiles
+            .iter()
+            .map(|(name, _, _)| &name[..])
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// The default options with the settings applied.
+    pub fn options(&self) -> Result<Options, MyError> {
+        let mut opts = Options::new();
+        for (origin, table) in &self.layers {
+            for (key, value) in table {
+                opts = apply(opts, key, value).map_err(|e| {
+                    MyError::Usage(format!("Invalid setting `{}` in {}: {}", key, origin, e))
+                })?;
+            }
+        }
+        Ok(opts)
+    }
+
+    /// The effective configuration `opts` as TOML, listing where the
+    /// settings came from.
+    pub fn dump(&self, opts: &Options) -> String {
+        let mut out = String::from("# from: built-in defaults\n");
+        for (origin, _) in &self.layers {
+            out.push_str(&format!("# from: {}\n", origin));
+        }
+        out.push_str("# from: command line\n");
+        out.push_str(&settings(opts).to_string());
+        out
+    }
+}
+
+/// `$XDG_CONFIG_HOME/rusty-html-extractor/config.toml`, if it exists.
+fn user_config() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".config")))?;
+    let path = config_home.join("rusty-html-extractor").join("config.toml");
+    path.is_file().then_some(path)
+}
+
+/// The nearest project configuration, if there is one.
+fn project_config() -> Option<PathBuf> {
+    let dir = std::env::current_dir().ok()?;
+    dir.ancestors()
+        .map(|d| d.join(PROJECT_CONFIG))
+        .find(|p| p.is_file())
+}
+
+/// Name of the environment variable of a setting, e.g. `RHE_MAX_DEPTH`.
+fn env_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.to_uppercase().replace('-', "_"))
+}
+
+/// Set the option `key` to `value`.
+fn apply(opts: Options, key: &str, value: &Value) -> Result<Options, String> {
+    Ok(match key {
+        "width" => opts.set_width(number(value)?),
+        "artifacts" => opts.set_artifacts(boolean(value)?),
+        "format" => opts.set_format(one_of(value, FORMATS)?),
+        "output" => opts.set_output_text_file(string(value)?),
+        "rest" => opts.set_output_dir(string(value)?),
+        "html" => opts.set_html_patterns(list(value)?),
+        "not-html" => opts.set_other_patterns(list(value)?),
+        "keep-temp" => opts.set_keep_temp(boolean(value)?),
+        "epub" => opts.set_epub(boolean(value)?),
+        "recursive" => opts.set_recursive(boolean(value)?),
+        "max-depth" => opts.set_max_depth(number(value)?),
+        "links" => opts.set_markdown_links(one_of(value, LINK_STYLES)?),
+        "tables" => opts.set_markdown_tables(one_of(value, TABLE_STYLES)?),
+        "whitespace" => opts.set_whitespace(one_of(value, WHITESPACE_POLICIES)?),
+        "preset" => opts.set_preset(one_of(value, PRESETS)?),
+        "header" => opts.set_header(string(value)?),
+        "footer" => opts.set_footer(string(value)?),
+        "separator" => opts.set_separator(string(value)?),
+        "split" => opts.set_split_dir(string(value)?),
+        "input-encoding" => opts.set_input_encoding(string(value)?),
+        "main-content" => opts.set_main_content(boolean(value)?),
+        "select" => opts.set_select(string(value)?),
+        "exclude" => opts.set_exclude(string(value)?),
+        "include" => opts.set_include_patterns(list(value)?),
+        "copy" => opts.set_copy_patterns(list(value)?),
+        "max-total-size" => opts.set_max_total_size(size(value)?),
+        "max-entry-size" => opts.set_max_entry_size(size(value)?),
+        "max-entries" => opts.set_max_entries(number(value)?),
+        "max-ratio" => opts.set_max_ratio(number(value)?),
+        "skip-over-limit" => opts.set_skip_over_limit(boolean(value)?),
+        "jobs" => opts.set_jobs(number(value)?),
+        "keep-going" => opts.set_keep_going(boolean(value)?),
+        _ => return Err("no such setting".into()),
+    })
+}
+
+/// Every setting of `opts`, leaving out the unset ones.
+fn settings(opts: &Options) -> Table {
+    let mut table = Table::new();
+    let mut set = |key: &str, value: Value| {
+        table.insert(key.to_string(), value);
+    };
+    let strings = |values: &[String]| Value::from(values.to_vec());
+    set("width", Value::from(opts.width));
+    set("artifacts", Value::from(opts.file_artifacts));
+    set("format", Value::from(&opts.output_format[..]));
+    set("output", Value::from(&opts.output_text_file[..]));
+    set("rest", Value::from(&opts.output_dir[..]));
+    set("html", strings(&opts.html_patterns));
+    set("not-html", strings(&opts.other_patterns));
+    set("keep-temp", Value::from(opts.keep_temp));
+    set("epub", Value::from(opts.epub));
+    set("recursive", Value::from(opts.recursive));
+    set("max-depth", Value::from(opts.max_depth));
+    set("links", Value::from(&opts.markdown_links[..]));
+    set("tables", Value::from(&opts.markdown_tables[..]));
+    set("whitespace", Value::from(&opts.whitespace[..]));
+    let optional = [
+        ("preset", &opts.preset),
+        ("header", &opts.header),
+        ("footer", &opts.footer),
+        ("separator", &opts.separator),
+        ("split", &opts.split_dir),
+        ("input-encoding", &opts.input_encoding),
+        ("select", &opts.select),
+        ("exclude", &opts.exclude),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            set(key, Value::from(&value[..]));
+        }
+    }
+    set("main-content", Value::from(opts.main_content));
+    set("include", strings(&opts.include_patterns));
+    set("copy", strings(&opts.copy_patterns));
+    set("max-total-size", integer(opts.max_total_size));
+    set("max-entry-size", integer(opts.max_entry_size));
+    set("max-entries", integer(opts.max_entries));
+    set("max-ratio", integer(opts.max_ratio));
+    set("skip-over-limit", Value::from(opts.skip_over_limit));
+    set("jobs", integer(opts.jobs as u64));
+    set("keep-going", Value::from(opts.keep_going));
+    table
+}
+
+/// TOML integers are signed, larger numbers are written as strings.
+fn integer(value: u64) -> Value {
+    i64::try_from(value).map_or_else(|_| Value::from(value.to_string()), Value::from)
+}
+
+fn string(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err("expected a string".into()),
+    }
+}
+
+fn one_of(value: &Value, allowed: &[&str]) -> Result<String, String> {
+    let value = string(value)?;
+    match allowed.contains(&&value[..]) {
+        true => Ok(value),
+        false => Err(format!("expected one of {}", allowed.join(", "))),
+    }
+}
+
+fn boolean(value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        Value::String(s) => match &s.to_ascii_lowercase()[..] {
+            "true" | "yes" | "on" | "1" => Ok(true),
+            "false" | "no" | "off" | "0" => Ok(false),
+            _ => Err("expected true or false".into()),
+        },
+        _ => Err("expected true or false".into()),
+    }
+}
+
+fn number<T: TryFrom<u64>>(value: &Value) -> Result<T, String> {
+    let number = match value {
+        Value::Integer(i) => u64::try_from(*i).ok(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    number
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| "expected a non-negative number".into())
+}
+
+fn size(value: &Value) -> Result<u64, String> {
+    match value {
+        Value::String(s) => parse_size(s).map_err(|_| "expected a size such as 64M".into()),
+        value => number(value),
+    }
+}
+
+fn list(value: &Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Array(values) => values.iter().map(string).collect(),
+        value => Ok(string(value)?
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(ToString::to_string)
+            .collect()),
+    }
+}
+
+/// Parse a size in bytes with an optional `K`, `M` or `G` suffix.
+pub fn parse_size(value: &str) -> Result<u64, MyError> {
+    let value = value.trim();
+    let (number, shift) = match value.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], 10),
+        Some('M') => (&value[..value.len() - 1], 20),
+        Some('G') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    number
+        .trim()
+        .parse::<u64>()?
+        .checked_mul(1 << shift)
+        .ok_or_else(|| MyError::Usage(format!("Size {} is too large", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG);
+        std::fs::write(
+            &path,
+            r#"
+width = 50
+format = "rich"
+tables = "html"
+whitespace = "collapse"
+
+[profiles.p]
+format = "markdown"
+whitespace = "strip"
+"#,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.read(&path).unwrap();
+        std::env::set_var("RHE_WIDTH", "60");
+        std::env::set_var("RHE_FORMAT", "plain");
+        config.read_env();
+        std::env::remove_var("RHE_WIDTH");
+        std::env::remove_var("RHE_FORMAT");
+        config.select("p").unwrap();
+        // The command line is applied over the configured options.
+        let opts = config.options().unwrap().set_whitespace("preserve");
+        assert_eq!(opts.markdown_tables, "html");
+        assert_eq!(opts.width, 60);
+        assert_eq!(opts.output_format, "markdown");
+        assert_eq!(opts.whitespace, "preserve");
+        assert!(config.select("q").is_err());
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let mut config = Config::default();
+        let mut layer = Table::new();
+        layer.insert("preset".into(), Value::from("fancy"));
+        config.layers.push(("test".into(), layer));
+        assert!(config.options().is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! Transformations of the HTML document applied before it is rendered.
+
+use crate::MyError;
+use ego_tree::{NodeId, NodeRef};
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::HashMap;
+
+/// Elements which never belong to the main content.
+const BOILERPLATE_TAGS: &[&str] = &[
+    "nav", "aside", "footer", "form", "script", "style", "noscript", "iframe", "button", "dialog",
+    "menu",
+];
+
+/// Elements which may hold the main content.
+const CANDIDATE_TAGS: &[&str] = &["article", "main", "section", "div", "td", "body"];
+
+/// Elements whose text is scored as a paragraph.
+const PARAGRAPH_TAGS: &[&str] = &["p", "pre", "blockquote", "td", "li", "h2", "h3"];
+
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "body", "content", "entry", "main", "page", "post", "text", "blog", "story",
+];
+
+const NEGATIVE_HINTS: &[&str] = &[
+    "ad-",
+    "ads",
+    "banner",
+    "breadcrumb",
+    "comment",
+    "cookie",
+    "consent",
+    "footer",
+    "header",
+    "masthead",
+    "menu",
+    "modal",
+    "nav",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+    "subscribe",
+    "widget",
+];
+
+/// Parse a CSS selector list such as `article, main, #content`.
+pub(crate) fn selector(css: &str) -> Result<Selector, MyError> {
+    Selector::parse(css).map_err(|_| MyError::Usage(format!("Invalid CSS selector: {}", css)))
+}
+
+/// Remove every element matching `exclude`, then keep only the elements
+/// matching `select`, in document order.
+pub(crate) fn filter(html: &str, select: Option<&Selector>, exclude: Option<&Selector>) -> String {
+    let mut document = Html::parse_document(html);
+    if let Some(exclude) = exclude {
+        let excluded = document.select(exclude).map(|e| e.id()).collect::<Vec<_>>();
+        for id in excluded {
+            if let Some(mut node) = document.tree.get_mut(id) {
+                node.detach();
+            }
+        }
+    }
+    let select = match select {
+        Some(select) => select,
+        None => return document.html(),
+    };
+    let selected = document.select(select).collect::<Vec<_>>();
+    let mut body = String::new();
+    for element in &selected {
+        // Elements inside another selected one are already part of its html.
+        let nested = element
+            .ancestors()
+            .any(|a| selected.iter().any(|s| s.id() == a.id()));
+        if !nested {
+            body.push_str(&element.html());
+        }
+    }
+    format!("<html><body>{}</body></html>", body)
+}
+
+/// Put `marker` on the blank lines inside `<pre>` elements, so they can be
+/// told apart once the document is rendered.
+pub(crate) fn mark_preformatted(html: &str, marker: char) -> String {
+    let mut document = Html::parse_document(html);
+    let pre = Selector::parse("pre").unwrap();
+    let texts = document
+        .select(&pre)
+        .flat_map(|e| e.descendants())
+        .filter(|n| n.value().is_text())
+        .map(|n| n.id())
+        .collect::<Vec<_>>();
+    for id in texts {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            if let Node::Text(text) = node.value() {
+                // The first and last line continue the text around the node.
+                let lines = text.split('\n').collect::<Vec<_>>();
+                let marked = lines
+                    .iter()
+                    .enumerate()
+                    .map(|(i, line)| {
+                        if i > 0 && i + 1 < lines.len() && line.trim().is_empty() {
+                            format!("{}{}", line, marker)
+                        } else {
+                            line.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                text.text = marked.into();
+            }
+        }
+    }
+    document.html()
+}
+
+/// Keep only the main content of a page, dropping navigation, banners,
+/// footers and sidebars.
+///
+/// Blocks are scored by the amount of paragraph text they hold, weighted by
+/// their link density and by hints in their `class` and `id`. The best
+/// scoring block is kept with its boilerplate descendants removed. Documents
+/// without any scorable text are returned unchanged.
+pub(crate) fn main_content(html: &str) -> String {
+    let mut document = Html::parse_document(html);
+    let best = match best_candidate(&document) {
+        Some(best) => best,
+        None => return html.to_string(),
+    };
+    let boilerplate = document
+        .tree
+        .get(best)
+        .into_iter()
+        .flat_map(|n| n.descendants())
+        .filter(|n| n.id() != best && is_boilerplate(*n))
+        .map(|n| n.id())
+        .collect::<Vec<_>>();
+    for id in boilerplate {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+    document
+        .tree
+        .get(best)
+        .and_then(ElementRef::wrap)
+        .map(|e| e.html())
+        .unwrap_or_else(|| html.to_string())
+}
+
+fn best_candidate(document: &Html) -> Option<NodeId> {
+    let mut scores: HashMap<NodeId, f64> = HashMap::new();
+    for paragraph in document.tree.root().descendants() {
+        if !has_tag(paragraph, PARAGRAPH_TAGS) {
+            continue;
+        }
+        let text = text_of(paragraph);
+        let length = text.trim().chars().count();
+        if length < 25 {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (length as f64 / 100.0).min(3.0);
+        let ancestors = paragraph
+            .ancestors()
+            .filter(|a| has_tag(*a, CANDIDATE_TAGS))
+            .take(3);
+        for (level, ancestor) in ancestors.enumerate() {
+            let share = match level {
+                0 => 1.0,
+                1 => 0.5,
+                _ => 1.0 / (level as f64 * 3.0),
+            };
+            *scores.entry(ancestor.id()).or_insert(0.0) += score * share;
+        }
+    }
+    scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let node = document.tree.get(id)?;
+            let weight = 1.0 + class_weight(node) / 100.0 + tag_weight(node);
+            Some((id, score * weight.max(0.1) * (1.0 - link_density(node))))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id)
+}
+
+fn is_boilerplate(node: NodeRef<Node>) -> bool {
+    if has_tag(node, BOILERPLATE_TAGS) {
+        return true;
+    }
+    let element = match node.value().as_element() {
+        Some(e) => e,
+        None => return false,
+    };
+    if element.attr("aria-hidden") == Some("true") || element.attr("role") == Some("navigation") {
+        return true;
+    }
+    class_weight(node) < 0.0 && (link_density(node) > 0.33 || text_of(node).trim().len() < 200)
+}
+
+/// -25 for every negative and +25 for every positive hint in the class and id.
+fn class_weight(node: NodeRef<Node>) -> f64 {
+    let element = match node.value().as_element() {
+        Some(e) => e,
+        None => return 0.0,
+    };
+    let hints = format!(
+        "{} {}",
+        element.attr("class").unwrap_or_default(),
+        element.attr("id").unwrap_or_default()
+    )
+    .to_lowercase();
+    let positive = POSITIVE_HINTS.iter().filter(|h| hints.contains(*h)).count();
+    let negative = NEGATIVE_HINTS.iter().filter(|h| hints.contains(*h)).count();
+    25.0 * (positive as f64 - negative as f64)
+}
+
+fn tag_weight(node: NodeRef<Node>) -> f64 {
+    match node.value().as_element().map(|e| e.name()) {
+        Some("article") | Some("main") => 0.25,
+        Some("body") => -0.25,
+        _ => 0.0,
+    }
+}
+
+/// Share of the text of `node` which is inside links.
+fn link_density(node: NodeRef<Node>) -> f64 {
+    let total = text_of(node).chars().count();
+    if total == 0 {
+        return 0.0;
+    }
+    let links = node
+        .descendants()
+        .filter(|n| has_tag(*n, &["a"]))
+        .map(|a| text_of(a).chars().count())
+        .sum::<usize>();
+    links as f64 / total as f64
+}
+
+fn text_of(node: NodeRef<Node>) -> String {
+    node.descendants()
+        .filter_map(|n| n.value().as_text().map(|t| &**t))
+        .collect()
+}
+
+fn has_tag(node: NodeRef<Node>, tags: &[&str]) -> bool {
+    node.value()
+        .as_element()
+        .map(|e| tags.contains(&e.name()))
+        .unwrap_or(false)
+}
@@ -0,0 +1,62 @@
+//! Character encoding detection and transcoding of HTML documents to UTF-8.
+
+use crate::sniff::SNIFF_LEN;
+use crate::MyError;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Look up an encoding by any of its WHATWG labels, e.g. `latin1` or `sjis`.
+pub(crate) fn for_label(label: &str) -> Result<&'static Encoding, MyError> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| MyError::Usage(format!("Unknown input encoding: {}", label)))
+}
+
+/// Pick the encoding of an HTML document.
+///
+/// In order of preference this is the `forced` encoding, a byte order mark,
+/// the `<meta charset>`/`http-equiv` declaration and finally a statistical
+/// guess from the content.
+pub(crate) fn detect(html: &[u8], forced: Option<&'static Encoding>) -> &'static Encoding {
+    if let Some(encoding) = forced {
+        return encoding;
+    }
+    if let Some((encoding, _)) = Encoding::for_bom(html) {
+        return encoding;
+    }
+    if let Some(encoding) = declared(html) {
+        // A document which could declare itself as UTF-16 is not UTF-16.
+        if encoding == UTF_16LE || encoding == UTF_16BE {
+            return UTF_8;
+        }
+        return encoding;
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(html, true);
+    detector.guess(None, true)
+}
+
+/// Transcode an HTML document to UTF-8, returning the text and the encoding
+/// it was decoded from.
+pub(crate) fn decode(
+    html: &[u8],
+    forced: Option<&'static Encoding>,
+) -> (String, &'static Encoding) {
+    let encoding = detect(html, forced);
+    let (text, _) = encoding.decode_with_bom_removal(html);
+    (text.into_owned(), encoding)
+}
+
+/// Encoding declared by a `charset=` attribute in the leading bytes.
+fn declared(html: &[u8]) -> Option<&'static Encoding> {
+    let head = html[..html.len().min(SNIFF_LEN)].to_ascii_lowercase();
+    let start = head
+        .windows(b"charset=".len())
+        .position(|w| w == b"charset=")?
+        + b"charset=".len();
+    let value = head[start..]
+        .iter()
+        .skip_while(|b| matches!(b, b'"' | b'\'' | b' '))
+        .take_while(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b':' | b'.'))
+        .copied()
+        .collect::<Vec<u8>>();
+    Encoding::for_label(&value)
+}
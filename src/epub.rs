@@ -0,0 +1,182 @@
+//! Reading order and metadata of EPUB publications.
+//!
+//! `META-INF/container.xml` points at the OPF package document, whose spine
+//! lists the content documents in reading order.
+
+use crate::MyError;
+use std::io::{Read, Seek};
+use zip::read::ZipArchive;
+
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+const MIMETYPE_PATH: &str = "mimetype";
+const EPUB_MIMETYPE: &str = "application/epub+zip";
+/// Packaging files are read before the entry limits apply, so they get a
+/// fixed one. Package documents of large publications stay well below it.
+const MAX_PACKAGE_SIZE: u64 = 16 << 20;
+
+/// Publication metadata from the OPF package document.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct EpubMetadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub language: Option<String>,
+}
+
+impl EpubMetadata {
+    /// Header written in front of the extracted text.
+    pub fn header(&self) -> String {
+        let mut header = String::new();
+        if let Some(title) = &self.title {
+            header.push_str(&format!("# title: {}\n", title));
+        }
+        if !self.authors.is_empty() {
+            header.push_str(&format!("# author: {}\n", self.authors.join(", ")));
+        }
+        if let Some(language) = &self.language {
+            header.push_str(&format!("# language: {}\n", language));
+        }
+        header
+    }
+}
+
+/// The parts of an EPUB needed to extract it in reading order.
+#[derive(Clone, Debug)]
+pub(crate) struct Epub {
+    pub metadata: EpubMetadata,
+    /// Archive paths of the spine items, in reading order.
+    pub spine: Vec<String>,
+    /// Archive paths of the packaging files, which are neither text nor content.
+    pub package: Vec<String>,
+}
+
+/// Does the archive look like an EPUB publication?
+pub(crate) fn is_epub<R: Read + Seek>(archive: &mut ZipArchive<R>) -> bool {
+    if archive.file_names().all(|n| n != MIMETYPE_PATH) {
+        return archive.file_names().any(|n| n == CONTAINER_PATH);
+    }
+    read_entry(archive, MIMETYPE_PATH)
+        .map(|m| m.trim() == EPUB_MIMETYPE)
+        .unwrap_or(false)
+}
+
+pub(crate) fn read<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Epub, MyError> {
+    let container = read_entry(archive, CONTAINER_PATH)?;
+    let container = roxmltree::Document::parse(&container)?;
+    let opf_path = container
+        .descendants()
+        .find(|n| n.has_tag_name("rootfile"))
+        .and_then(|n| n.attribute("full-path"))
+        .ok_or_else(|| MyError::Corrupt("EPUB container does not name a package document".into()))?
+        .to_string();
+
+    let opf = read_entry(archive, &opf_path)?;
+    let opf = roxmltree::Document::parse(&opf)?;
+    let base = match opf_path.rfind('/') {
+        Some(i) => &opf_path[..=i],
+        None => "",
+    };
+
+    let metadata = opf
+        .descendants()
+        .find(|n| n.has_tag_name("metadata"))
+        .map(|m| EpubMetadata {
+            title: child_text(m, "title").next(),
+            authors: child_text(m, "creator").collect(),
+            language: child_text(m, "language").next(),
+        })
+        .unwrap_or_default();
+
+    let manifest = opf
+        .descendants()
+        .filter(|n| n.has_tag_name("item"))
+        .filter_map(|n| Some((n.attribute("id")?, n.attribute("href")?)))
+        .collect::<Vec<_>>();
+    let spine = opf
+        .descendants()
+        .filter(|n| n.has_tag_name("itemref"))
+        .filter_map(|n| n.attribute("idref"))
+        .filter_map(|idref| manifest.iter().find(|(id, _)| *id == idref))
+        .map(|(_, href)| resolve(base, href))
+        .collect();
+
+    Ok(Epub {
+        metadata,
+        spine,
+        package: vec![
+            MIMETYPE_PATH.to_string(),
+            CONTAINER_PATH.to_string(),
+            opf_path,
+        ],
+    })
+}
+
+/// Read a packaging file, at most [`MAX_PACKAGE_SIZE`] bytes of it.
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<String, MyError> {
+    let too_large = || {
+        MyError::Limit(format!(
+            "{}: EPUB packaging file exceeds {} bytes",
+            name, MAX_PACKAGE_SIZE
+        ))
+    };
+    let file = archive.by_name(name)?;
+    if file.size() > MAX_PACKAGE_SIZE {
+        return Err(too_large());
+    }
+    let mut content = String::new();
+    file.take(MAX_PACKAGE_SIZE + 1)
+        .read_to_string(&mut content)?;
+    if content.len() as u64 > MAX_PACKAGE_SIZE {
+        return Err(too_large());
+    }
+    Ok(content)
+}
+
+fn child_text<'a>(
+    node: roxmltree::Node<'a, 'a>,
+    name: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    node.children()
+        .filter(move |n| n.tag_name().name() == name)
+        .filter_map(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Resolve a manifest `href` relative to the directory of the package document.
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let mut parts: Vec<String> = Vec::new();
+    for part in format!("{}{}", base, percent_decode(href)).split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part.to_string()),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+
+/// Everything that can go wrong during an extraction.
+#[derive(Debug)]
+pub enum MyError {
+    /// An invalid option or argument.
+    Usage(String),
+    /// An invalid glob pattern.
+    Glob(globset::Error),
+    /// An option expecting a number got something else.
+    Number(std::num::ParseIntError),
+    /// The input could not be opened or read.
+    Input {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The input, or an archive inside it, is malformed.
+    Corrupt(String),
+    /// A malformed ZIP archive.
+    Zip(zip::result::ZipError),
+    /// A malformed XML document of an EPUB package.
+    Xml(roxmltree::Error),
+    /// An output file could not be written.
+    Output {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A resource limit was exceeded.
+    Limit(String),
+    /// Processing a single entry of the input failed.
+    Entry {
+        /// Name of the entry inside the input.
+        name: String,
+        /// Relative path the entry would be extracted to.
+        path: Option<PathBuf>,
+        source: Box<MyError>,
+    },
+    /// Entries which failed while the run kept going, in input order.
+    Failures(Vec<MyError>),
+    /// Any other I/O error.
+    Io(std::io::Error),
+}
+
+impl MyError {
+    /// Exit code of the command line tool for this class of error:
+    ///
+    /// - `1` for any other error
+    /// - `2` for invalid arguments
+    /// - `3` when the input can not be read
+    /// - `4` for a corrupt input or a limit being exceeded
+    /// - `5` when an output can not be written
+    /// - `6` when some entries failed but the run kept going
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Usage(_) | Self::Glob(_) | Self::Number(_) => 2,
+            Self::Input { .. } => 3,
+            Self::Corrupt(_) | Self::Zip(_) | Self::Xml(_) | Self::Limit(_) => 4,
+            Self::Output { .. } => 5,
+            // Reading an entry fails when its compressed data is damaged.
+            Self::Entry { source, .. } if matches!(**source, Self::Io(_)) => 4,
+            Self::Entry { source, .. } => source.exit_code(),
+            Self::Failures(_) => 6,
+            Self::Io(e) if is_corrupt(e) => 4,
+            Self::Io(_) => 1,
+        }
+    }
+
+    /// Attribute the error to the entry `name`, unless it already names the
+    /// entry or output it is about.
+    pub(crate) fn in_entry(self, name: &str, path: Option<&Path>) -> Self {
+        match self {
+            Self::Entry { .. } | Self::Output { .. } | Self::Limit(_) | Self::Usage(_) => self,
+            source => Self::Entry {
+                name: name.to_string(),
+                path: path.map(Path::to_path_buf),
+                source: Box::new(source),
+            },
+        }
+    }
+}
+
+/// Does the I/O error mean damaged or truncated data rather than a failure
+/// to read it? Decompressors report damaged data like this.
+pub(crate) fn is_corrupt(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::InvalidData
+            | std::io::ErrorKind::InvalidInput
+            | std::io::ErrorKind::UnexpectedEof
+    )
+}
+
+/// Attach the file an I/O error happened on.
+pub(crate) trait Context<T> {
+    /// The error happened while reading the input at `path`.
+    fn reading(self, path: &Path) -> Result<T, MyError>;
+    /// The error happened while writing the output at `path`.
+    fn writing(self, path: &Path) -> Result<T, MyError>;
+}
+
+impl<T, E: Into<MyError>> Context<T> for Result<T, E> {
+    fn reading(self, path: &Path) -> Result<T, MyError> {
+        self.map_err(|e| match e.into() {
+            MyError::Io(source) => MyError::Input {
+                path: path.to_path_buf(),
+                source,
+            },
+            e => e,
+        })
+    }
+
+    fn writing(self, path: &Path) -> Result<T, MyError> {
+        self.map_err(|e| match e.into() {
+            MyError::Io(source) => MyError::Output {
+                path: path.to_path_buf(),
+                source,
+            },
+            e => e,
+        })
+    }
+}
+
+impl From<zip::result::ZipError> for MyError {
+    fn from(value: zip::result::ZipError) -> Self {
+        match value {
+            zip::result::ZipError::Io(e) => e.into(),
+            e => Self::Zip(e),
+        }
+    }
+}
+
+impl From<globset::Error> for MyError {
+    fn from(value: globset::Error) -> Self {
+        Self::Glob(value)
+    }
+}
+
+impl From<roxmltree::Error> for MyError {
+    fn from(value: roxmltree::Error) -> Self {
+        Self::Xml(value)
+    }
+}
+
+impl From<serde_json::Error> for MyError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Io(value.into())
+    }
+}
+
+impl From<std::io::Error> for MyError {
+    fn from(value: std::io::Error) -> Self {
+        match value
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<crate::limits::LimitExceeded>())
+        {
+            Some(limit) => Self::Limit(limit.0.clone()),
+            None => Self::Io(value),
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for MyError {
+    fn from(value: std::num::ParseIntError) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl std::fmt::Display for MyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usage(e) => write!(f, "{}", e),
+            Self::Glob(e) => write!(f, "Invalid glob pattern: {}", e),
+            Self::Number(e) => write!(f, "Not a number: {}", e),
+            Self::Input { path, source } => {
+                write!(f, "Cannot read {}: {}", path.display(), source)
+            }
+            Self::Corrupt(e) => write!(f, "{}", e),
+            Self::Zip(e) => write!(f, "Corrupt zip archive: {}", e),
+            Self::Xml(e) => write!(f, "Malformed XML document: {}", e),
+            Self::Output { path, source } => {
+                write!(f, "Cannot write {}: {}", path.display(), source)
+            }
+            Self::Limit(e) => write!(f, "{}", e),
+            Self::Entry { name, source, .. } => write!(f, "{}: {}", name, source),
+            Self::Failures(failures) => {
+                match failures.len() {
+                    1 => write!(f, "1 entry failed")?,
+                    n => write!(f, "{} entries failed", n)?,
+                }
+                for failure in failures {
+                    write!(f, "\n  {}", failure)?;
+                }
+                Ok(())
+            }
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for MyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Glob(e) => Some(e),
+            Self::Number(e) => Some(e),
+            Self::Input { source, .. } | Self::Output { source, .. } | Self::Io(source) => {
+                Some(source)
+            }
+            Self::Zip(e) => Some(e),
+            Self::Xml(e) => Some(e),
+            Self::Entry { source, .. } => Some(source.as_ref()),
+            Self::Usage(_) | Self::Corrupt(_) | Self::Limit(_) | Self::Failures(_) => None,
+        }
+    }
+}
@@ -0,0 +1,667 @@
+use crate::dom;
+use crate::encoding;
+use crate::epub::EpubMetadata;
+use crate::error::Context;
+use crate::filter::EntryFilter;
+use crate::limits::Budget;
+use crate::listing::{EntryInfo, FindSink, ListSink, Summary};
+use crate::markdown;
+use crate::options::STDIO;
+use crate::pool::{self, Job, Pool};
+use crate::sink::{FileSink, MemorySink, Sink};
+use crate::sniff::{Sniffer, SNIFF_LEN};
+use crate::source::{self, Entry, Source};
+use crate::template::Frame;
+use crate::workdir::WorkDir;
+use crate::{MyError, Options};
+use encoding_rs::Encoding;
+use html2text::render::text_renderer::{PlainDecorator, RichDecorator, TrivialDecorator};
+use scraper::{Html, Selector};
+use std::cell::RefCell;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Entry name of a single document read from standard input.
+const STDIN_NAME: &str = "stdin";
+
+/// Text extracted from a single HTML entry of the archive.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Document {
+    /// Name of the entry inside the archive.
+    pub name: String,
+    /// Relative path the entry would be extracted to.
+    pub path: PathBuf,
+    /// Rendered text of the entry.
+    pub text: String,
+    /// Content of the `<title>` element.
+    pub title: Option<String>,
+    /// Character encoding the HTML source was decoded from.
+    pub charset: String,
+    /// Size of the HTML source in bytes.
+    pub size: u64,
+    /// CRC-32 checksum of the HTML source.
+    pub crc32: u32,
+    /// Target URI of the web archive record the document was captured in.
+    pub url: Option<String>,
+    /// Capture date of the web archive record, as an ISO 8601 timestamp.
+    pub date: Option<String>,
+    /// Last modification time of the entry, as an ISO 8601 timestamp.
+    pub mtime: Option<String>,
+}
+
+/// What happened besides the output of a run which did not abort.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Entries which failed in keep-going mode, as [`MyError::Entry`] errors
+    /// in input order.
+    pub failures: Vec<MyError>,
+    /// Entries going over a limit which were skipped with `skip_over_limit`,
+    /// as [`MyError::Limit`] errors.
+    pub skipped: Vec<MyError>,
+    /// The working directory, if it was kept with `keep_temp`.
+    pub work_dir: Option<PathBuf>,
+}
+
+impl Document {
+    pub fn word_count(&self) -> usize {
+        self.text.split_whitespace().count()
+    }
+}
+
+/// Entry point of the library, turns the HTML inside an archive or directory
+/// into text.
+#[derive(Clone, Debug)]
+pub struct Extractor {
+    opts: Options,
+}
+
+impl Extractor {
+    pub fn new(opts: Options) -> Self {
+        Self { opts }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.opts
+    }
+
+    /// Render every HTML entry of the input archive and return the text in memory.
+    ///
+    /// Non-HTML entries are skipped and nothing is written to the output paths.
+    /// For EPUB input the documents are returned in reading order. In
+    /// keep-going mode the entries which failed are in the report.
+    pub fn documents(&self) -> Result<(Vec<Document>, Report), MyError> {
+        let work_dir = WorkDir::new(self.opts.keep_temp)?;
+        let mut sink = MemorySink::default();
+        let report = self.walk(&work_dir, &mut sink)?;
+        Ok((sink.documents, report))
+    }
+
+    /// Every entry of the input and what [`Extractor::run`] would do with it,
+    /// without rendering or copying anything.
+    pub fn entries(&self) -> Result<(Vec<EntryInfo>, Report), MyError> {
+        let (list, report) = self.list()?;
+        Ok((list.entries, report))
+    }
+
+    /// Format, entry counts and sizes of the input.
+    pub fn inspect(&self) -> Result<(Summary, Report), MyError> {
+        let (list, report) = self.list()?;
+        let mut summary = Summary {
+            format: list.format,
+            metadata: list.metadata,
+            ..Default::default()
+        };
+        for entry in &list.entries {
+            summary.add(entry);
+        }
+        Ok((summary, report))
+    }
+
+    fn list(&self) -> Result<(ListSink, Report), MyError> {
+        let work_dir = WorkDir::new(self.opts.keep_temp)?;
+        let mut sink = ListSink::default();
+        let report = self.walk(&work_dir, &mut sink)?;
+        Ok((sink, report))
+    }
+
+    /// Render the single HTML entry called `name`, e.g. `OEBPS/ch1.xhtml` or
+    /// `inner.zip!/index.html` for an entry of a nested archive.
+    ///
+    /// Failures of other entries in keep-going mode only matter if the entry
+    /// was not found, maybe because it is inside an archive which failed.
+    pub fn find(&self, name: &str) -> Result<Document, MyError> {
+        let work_dir = WorkDir::new(self.opts.keep_temp)?;
+        let mut sink = FindSink {
+            name: name.to_string(),
+            found: None,
+            document: None,
+        };
+        let report = self.walk(&work_dir, &mut sink)?;
+        match (sink.document, sink.found) {
+            (Some(document), _) => Ok(document),
+            (None, None) if !report.failures.is_empty() => Err(MyError::Failures(report.failures)),
+            (None, Some(entry)) => Err(MyError::Usage(format!(
+                "{} is not rendered to text (kind {}, action {})",
+                name, entry.kind, entry.action
+            ))),
+            (None, None) => Err(MyError::Usage(format!("No entry {} in the input", name))),
+        }
+    }
+
+    /// Title, authors and language of the input, if it is an EPUB publication.
+    pub fn metadata(&self) -> Result<Option<EpubMetadata>, MyError> {
+        let work_dir = WorkDir::new(self.opts.keep_temp)?;
+        let sniffer = Sniffer::new(&self.opts.html_patterns, &self.opts.other_patterns)?;
+        Ok(self.open(&work_dir, &sniffer)?.metadata().cloned())
+    }
+
+    /// Write the text of all HTML entries into the output text file and copy the
+    /// rest of the entries into the output directory.
+    ///
+    /// The text is staged in a private working directory and only copied to the
+    /// output text file once the whole archive was processed. In split mode
+    /// every document gets its own file in the split directory instead.
+    ///
+    /// In keep-going mode the output is written even if some entries failed,
+    /// which are then listed in the report.
+    pub fn run(&self) -> Result<Report, MyError> {
+        self.opts.check()?;
+        let frame = Frame::new(&self.opts)?;
+        let work_dir = WorkDir::new(self.opts.keep_temp)?;
+        let (output_text, staged_text) = work_dir.file("html_text")?;
+        if self.opts.output_dir != STDIO {
+            std::fs::create_dir_all(&self.opts.output_dir)
+                .writing(Path::new(&self.opts.output_dir))?;
+        }
+        let mut sink = FileSink::new(output_text, &self.opts, frame);
+        let mut report = self.walk(&work_dir, &mut sink)?;
+        sink.finish()?;
+        report.work_dir = self.opts.keep_temp.then(|| work_dir.path().to_path_buf());
+        if self.opts.split_dir.is_some() {
+            return Ok(report);
+        }
+        let output_text_file = Path::new(&self.opts.output_text_file);
+        if self.opts.output_text_file == STDIO {
+            let mut stdout = std::io::stdout().lock();
+            std::io::copy(&mut std::fs::File::open(staged_text)?, &mut stdout)
+                .and_then(|_| stdout.flush())
+                .writing(output_text_file)?;
+        } else {
+            std::fs::copy(staged_text, output_text_file).writing(output_text_file)?;
+        }
+        Ok(report)
+    }
+
+    /// Open the input, standard input is spooled into the working directory
+    /// first as archives need to be seekable. `sniffer` tells whether an
+    /// input which is not an archive is a single HTML document.
+    fn open(&self, work_dir: &WorkDir, sniffer: &Sniffer) -> Result<Box<dyn Source>, MyError> {
+        if self.opts.input_file == STDIO {
+            let mut spool = work_dir.spool()?;
+            std::io::copy(&mut std::io::stdin().lock(), &mut spool).reading(Path::new(STDIO))?;
+            spool.seek(SeekFrom::Start(0))?;
+            return source::open_reader(
+                spool,
+                STDIN_NAME,
+                None,
+                self.opts.epub,
+                sniffer,
+                self.opts.max_total_size,
+            );
+        }
+        source::open(
+            Path::new(&self.opts.input_file),
+            self.opts.epub,
+            sniffer,
+            self.opts.max_total_size,
+        )
+    }
+
+    /// Stream every entry of the input exactly once, sniffing its leading
+    /// bytes and handing the entry to the sink.
+    fn walk(&self, work_dir: &WorkDir, sink: &mut dyn Sink) -> Result<Report, MyError> {
+        self.opts.check()?;
+        let input_encoding = match &self.opts.input_encoding {
+            Some(label) => Some(encoding::for_label(label)?),
+            None => None,
+        };
+        let rendering = Rendering {
+            input_encoding,
+            select: self.opts.select.as_deref().map(dom::selector).transpose()?,
+            exclude: self
+                .opts
+                .exclude
+                .as_deref()
+                .map(dom::selector)
+                .transpose()?,
+        };
+        let walk = Walk {
+            sniffer: Sniffer::new(&self.opts.html_patterns, &self.opts.other_patterns)?,
+            budget: Budget::new(&self.opts),
+            filter: EntryFilter::new(&self.opts.include_patterns, &self.opts.copy_patterns)?,
+            work_dir,
+            rendering: &rendering,
+            pool: None,
+            failures: RefCell::new(Vec::new()),
+            skipped: RefCell::new(Vec::new()),
+        };
+        let mut source = self.open(work_dir, &walk.sniffer)?;
+        sink.input(&source.kind())?;
+        if let Some(metadata) = source.metadata() {
+            sink.metadata(metadata)?;
+        }
+        let jobs = match self.opts.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs,
+        };
+        if jobs == 1 {
+            source.for_each_entry(&mut |entry| self.limited_entry(&walk, entry, sink, 0))?;
+            return Ok(walk.report());
+        }
+        pool::with_pool(jobs, &|job| self.document(&rendering, job), |pool| {
+            let walk = Walk {
+                pool: Some(pool),
+                ..walk
+            };
+            source.for_each_entry(&mut |entry| self.limited_entry(&walk, entry, sink, 0))?;
+            pool.finish(&mut |document| self.emit(&walk, document, sink))?;
+            Ok(walk.report())
+        })
+    }
+
+    /// Process an entry within the resource limits. Entries going over a
+    /// limit abort the run, or are reported and skipped if asked to.
+    fn limited_entry(
+        &self,
+        walk: &Walk,
+        entry: Result<Entry, MyError>,
+        sink: &mut dyn Sink,
+        depth: u32,
+    ) -> Result<(), MyError> {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => return self.failed(walk, e),
+        };
+        let name = entry.name.clone();
+        let path = entry.path.clone();
+        let res = match walk.budget.admit(&entry) {
+            Ok(true) => {
+                let mut content =
+                    walk.budget
+                        .reader(&entry.name, entry.compressed_size, entry.content);
+                let entry = Entry {
+                    content: &mut content,
+                    ..entry
+                };
+                self.entry(walk, entry, sink, depth)
+            }
+            Ok(false) => Ok(()),
+            Err(e) => Err(e),
+        };
+        match res {
+            Err(error @ MyError::Limit(_)) if self.opts.skip_over_limit => {
+                walk.skipped.borrow_mut().push(error);
+                Ok(())
+            }
+            Err(e) => self.failed(walk, e.in_entry(&name, path.as_deref())),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Record the failure of an entry in keep-going mode, any other error
+    /// aborts the run.
+    fn failed(&self, walk: &Walk, error: MyError) -> Result<(), MyError> {
+        match error {
+            MyError::Entry { .. } if self.opts.keep_going => {
+                walk.failures.borrow_mut().push(error);
+                Ok(())
+            }
+            error => Err(error),
+        }
+    }
+
+    /// Hand a rendered document to the sink.
+    fn emit(
+        &self,
+        walk: &Walk,
+        document: Result<Document, MyError>,
+        sink: &mut dyn Sink,
+    ) -> Result<(), MyError> {
+        match document {
+            Ok(document) => sink.html(document),
+            Err(error) => self.failed(walk, error),
+        }
+    }
+
+    fn entry(
+        &self,
+        walk: &Walk,
+        entry: Entry,
+        sink: &mut dyn Sink,
+        depth: u32,
+    ) -> Result<(), MyError> {
+        let info = |kind, action| EntryInfo {
+            name: entry.name.clone(),
+            path: entry.path.clone(),
+            size: entry.size,
+            compressed_size: entry.compressed_size,
+            method: entry.method.clone(),
+            kind,
+            action,
+        };
+        let fname = match &entry.path {
+            Some(p) => p.clone(),
+            None => {
+                sink.listed(info("unknown", "skip"));
+                return Ok(());
+            }
+        };
+        if walk.filter.is_excluded(&entry.name) {
+            sink.listed(info("unknown", "skip"));
+            return Ok(());
+        }
+
+        if entry.is_dir {
+            if walk.filter.is_active() {
+                sink.listed(info("dir", "skip"));
+                return Ok(());
+            }
+            if !sink.listed(info("dir", "dir")) {
+                return Ok(());
+            }
+            return sink.dir(&fname);
+        }
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        (&mut *entry.content)
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut head)?;
+        let format = source::detect(&head);
+        let is_html = walk.sniffer.is_html(&entry.name, &head);
+        let kind = match format {
+            _ if is_html => "html",
+            Some(source::Format::Zip) => "zip",
+            Some(source::Format::Tar(_)) => "tar",
+            Some(source::Format::Warc) => "warc",
+            None => "other",
+        };
+        let copied = walk.filter.is_included(&entry.name) && walk.filter.is_copied(&entry.name);
+        let mut content = std::io::Cursor::new(&head).chain(entry.content);
+        // Nested archives which are not included are not even spooled.
+        if !walk.filter.is_included(&entry.name) {
+            sink.listed(info(kind, "skip"));
+            return Ok(());
+        }
+        if self.opts.recursive && format.is_some() && depth >= self.opts.max_depth {
+            return Err(MyError::Limit(format!(
+                "{}: nested archive exceeds the nesting depth limit of {}",
+                entry.name, self.opts.max_depth
+            )));
+        }
+        if self.opts.recursive && format.is_some() {
+            let mut spool = walk.work_dir.spool()?;
+            std::io::copy(&mut content, &mut spool)?;
+            spool.seek(SeekFrom::Start(0))?;
+            return match source::open_reader(
+                spool.try_clone()?,
+                &entry.name,
+                None,
+                false,
+                &walk.sniffer,
+                self.opts.max_total_size,
+            ) {
+                Ok(nested) => {
+                    sink.listed(info(kind, "nested"));
+                    self.nested(walk, &entry.name, &fname, nested, sink, depth)
+                }
+                Err(_) if copied => {
+                    if !sink.listed(info(kind, "copy")) {
+                        return Ok(());
+                    }
+                    spool.seek(SeekFrom::Start(0))?;
+                    sink.other(&fname, &mut spool)
+                }
+                Err(_) => {
+                    sink.listed(info(kind, "skip"));
+                    Ok(())
+                }
+            };
+        }
+        if is_html {
+            if !sink.listed(info(kind, "text")) {
+                return Ok(());
+            }
+            let mut raw = Vec::new();
+            content.read_to_end(&mut raw)?;
+            let job = Job {
+                name: entry.name,
+                path: fname,
+                raw,
+                url: entry.url,
+                date: entry.date,
+                mtime: entry.mtime,
+            };
+            match walk.pool {
+                Some(pool) => pool.submit(job, &mut |document| self.emit(walk, document, sink)),
+                None => {
+                    let document = pool::caught(|job| self.document(walk.rendering, job), job);
+                    self.emit(walk, document, sink)
+                }
+            }
+        } else if copied {
+            if !sink.listed(info(kind, "copy")) {
+                return Ok(());
+            }
+            sink.other(&fname, &mut content)
+        } else {
+            sink.listed(info(kind, "skip"));
+            Ok(())
+        }
+    }
+
+    /// Process the entries of an archive found inside the input. Their names
+    /// and paths are prefixed with the archive's own, followed by a `!`.
+    fn nested(
+        &self,
+        walk: &Walk,
+        name: &str,
+        path: &Path,
+        mut nested: Box<dyn Source>,
+        sink: &mut dyn Sink,
+        depth: u32,
+    ) -> Result<(), MyError> {
+        let mut prefix = path.as_os_str().to_os_string();
+        prefix.push("!");
+        let prefix = PathBuf::from(prefix);
+        sink.input(&nested.kind())?;
+        if let Some(metadata) = nested.metadata() {
+            sink.metadata(metadata)?;
+        }
+        nested.for_each_entry(&mut |inner| {
+            let inner = match inner {
+                Ok(inner) => Ok(Entry {
+                    name: format!("{}!/{}", name, inner.name),
+                    path: inner.path.map(|p| prefix.join(p)),
+                    ..inner
+                }),
+                Err(MyError::Entry {
+                    name: inner,
+                    path,
+                    source,
+                }) => Err(MyError::Entry {
+                    name: format!("{}!/{}", name, inner),
+                    path: path.map(|p| prefix.join(p)),
+                    source,
+                }),
+                Err(e) => Err(e),
+            };
+            self.limited_entry(walk, inner, sink, depth + 1)
+        })
+    }
+
+    fn document(&self, rendering: &Rendering, job: Job) -> Result<Document, MyError> {
+        let (html, charset) = encoding::decode(&job.raw, rendering.input_encoding);
+        let title = title(&html);
+        let html = if rendering.select.is_some() || rendering.exclude.is_some() {
+            dom::filter(&html, rendering.select.as_ref(), rendering.exclude.as_ref())
+        } else {
+            html
+        };
+        let html = if self.opts.main_content {
+            dom::main_content(&html)
+        } else {
+            html
+        };
+        let text = render(&self.opts, html.as_bytes())
+            .map_err(|e| e.in_entry(&job.name, Some(&job.path)))?;
+        Ok(Document {
+            name: job.name,
+            path: job.path,
+            text,
+            title,
+            charset: charset.name().to_string(),
+            size: job.raw.len() as u64,
+            crc32: crc32fast::hash(&job.raw),
+            url: job.url,
+            date: job.date,
+            mtime: job.mtime,
+        })
+    }
+}
+
+/// State shared by all entries of a single walk over the input.
+struct Walk<'a> {
+    sniffer: Sniffer,
+    budget: Budget,
+    filter: EntryFilter,
+    work_dir: &'a WorkDir,
+    rendering: &'a Rendering,
+    /// Workers rendering the documents, `None` to render them in place.
+    pool: Option<&'a Pool>,
+    /// Entries which failed in keep-going mode.
+    failures: RefCell<Vec<MyError>>,
+    /// Entries going over a limit with skip-over-limit.
+    skipped: RefCell<Vec<MyError>>,
+}
+
+impl Walk<'_> {
+    fn report(self) -> Report {
+        Report {
+            failures: self.failures.into_inner(),
+            skipped: self.skipped.into_inner(),
+            work_dir: None,
+        }
+    }
+}
+
+/// Settings of the conversion of a document, shared with the workers.
+struct Rendering {
+    input_encoding: Option<&'static Encoding>,
+    select: Option<Selector>,
+    exclude: Option<Selector>,
+}
+
+/// Text of the first `<title>` element of the document.
+fn title(html: &str) -> Option<String> {
+    let selector = scraper::Selector::parse("title").ok()?;
+    Html::parse_document(html)
+        .select(&selector)
+        .next()
+        .map(|t| t.text().collect::<String>().trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Marks the blank lines of `<pre>` elements in the text rendered by
+/// html2text, which can not be told apart from other lines otherwise.
+const PRE_BLANK: char = '\u{E000}';
+
+/// Render HTML read from `input` with the decorator selected in `opts`.
+pub(crate) fn render(opts: &Options, mut input: impl Read) -> Result<String, MyError> {
+    let mut html = Vec::new();
+    input.read_to_end(&mut html)?;
+    let html = String::from_utf8_lossy(&html);
+    if opts.output_format == "markdown" {
+        let text = markdown::render(&html, &opts.markdown_links, &opts.markdown_tables);
+        return Ok(blank_lines(&text, &opts.whitespace, true));
+    }
+    let html = if opts.whitespace != "preserve" && html.to_ascii_lowercase().contains("<pre") {
+        dom::mark_preformatted(&html, PRE_BLANK)
+    } else {
+        html.into_owned()
+    };
+    let input = html.as_bytes();
+    let width = opts.width.try_into().unwrap_or(80);
+    let html_text = match &opts.output_format[..] {
+        "plain" => html2text::from_read_with_decorator(input, width, PlainDecorator::new()),
+        "rich" => html2text::from_read_with_decorator(input, width, RichDecorator::new()),
+        _ => html2text::from_read_with_decorator(input, width, TrivialDecorator::new()),
+    };
+    Ok(blank_lines(&html_text, &opts.whitespace, false).replace(PRE_BLANK, ""))
+}
+
+/// Apply the blank line `policy` to rendered text. Leading and trailing blank
+/// lines are always dropped and non-empty text ends with a newline. With
+/// `fenced` the text is Markdown, whose fenced code blocks are left alone.
+fn blank_lines(text: &str, policy: &str, fenced: bool) -> String {
+    let mut out = String::with_capacity(text.len() + 1);
+    let mut blanks = 0;
+    // The fence of the code block the line is in.
+    let mut fence: Option<&str> = None;
+    for line in text.lines() {
+        if let Some(open) = fence {
+            if line == open {
+                fence = None;
+            }
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        if line.trim().is_empty() {
+            blanks += 1;
+            continue;
+        }
+        if fenced && (line.starts_with("```") || line.starts_with("~~~")) {
+            let marker = &line[..1];
+            fence = Some(&line[..line.len() - line.trim_start_matches(marker).len()]);
+        }
+        if !out.is_empty() {
+            let kept = match policy {
+                "preserve" => blanks,
+                "strip" => 0,
+                _ => blanks.min(1),
+            };
+            out.push_str(&"\n".repeat(kept));
+        }
+        blanks = 0;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_line_policies() {
+        let text = "\n\none\n\n\n  \ntwo\n\n";
+        assert_eq!(blank_lines(text, "preserve", false), "one\n\n\n\ntwo\n");
+        assert_eq!(blank_lines(text, "collapse", false), "one\n\ntwo\n");
+        assert_eq!(blank_lines(text, "strip", false), "one\ntwo\n");
+    }
+
+    #[test]
+    fn fences_are_only_kept_in_markdown() {
+        let text = "a\n\n```rust\nb\n\n\nc\n```\n\n\nd\n";
+        assert_eq!(
+            blank_lines(text, "strip", true),
+            "a\n```rust\nb\n\n\nc\n```\nd\n"
+        );
+        assert_eq!(
+            blank_lines(text, "strip", false),
+            "a\n```rust\nb\nc\n```\nd\n"
+        );
+        let text = "a\n```\n\n\nb\n";
+        assert_eq!(blank_lines(text, "collapse", false), "a\n```\n\nb\n");
+    }
+}
@@ -0,0 +1,56 @@
+//! Glob based selection of the entries which are processed at all.
+
+use crate::sniff::glob_set;
+use crate::MyError;
+use globset::GlobSet;
+
+/// Decides which entries are rendered or copied and which are skipped.
+///
+/// Include patterns starting with `!` exclude the entries they match, e.g.
+/// `docs/**/*.html,!**/_static/**`. Entries are matched by their full name,
+/// nested archive entries included (`outer.zip!/page.html`).
+#[derive(Clone, Debug)]
+pub struct EntryFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    copy: Option<GlobSet>,
+}
+
+impl EntryFilter {
+    /// `include` selects the entries to process at all, `copy` the non-HTML
+    /// entries which are copied into the output directory. An empty list
+    /// selects everything.
+    pub fn new(include: &[String], copy: &[String]) -> Result<Self, MyError> {
+        let (exclude, include): (Vec<String>, Vec<String>) =
+            include.iter().cloned().partition(|p| p.starts_with('!'));
+        let exclude = exclude
+            .iter()
+            .map(|p| p[1..].to_string())
+            .collect::<Vec<_>>();
+        Ok(Self {
+            include: (!include.is_empty())
+                .then(|| glob_set(&include))
+                .transpose()?,
+            exclude: glob_set(&exclude)?,
+            copy: (!copy.is_empty()).then(|| glob_set(copy)).transpose()?,
+        })
+    }
+
+    /// Is any filter set up, in which case directories are only created for
+    /// the entries which end up being copied.
+    pub fn is_active(&self) -> bool {
+        self.include.is_some() || self.copy.is_some() || !self.exclude.is_empty()
+    }
+
+    pub fn is_excluded(&self, name: &str) -> bool {
+        self.exclude.is_match(name)
+    }
+
+    pub fn is_included(&self, name: &str) -> bool {
+        !self.is_excluded(name) && self.include.as_ref().is_none_or(|i| i.is_match(name))
+    }
+
+    pub fn is_copied(&self, name: &str) -> bool {
+        self.copy.as_ref().is_none_or(|c| c.is_match(name))
+    }
+}
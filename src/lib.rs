@@ -0,0 +1,39 @@
+//! Extract text from zipped HTML files.
+//!
+//! ```no_run
+//! use rusty_html_extractor::{Extractor, Options};
+//!
+//! let opts = Options::new().set_width(100).set_input_file("site.zip");
+//! let (documents, _) = Extractor::new(opts).documents().unwrap();
+//! for doc in documents {
+//!     println!("{}: {}", doc.name, doc.text);
+//! }
+//! ```
+
+mod config;
+mod dom;
+mod encoding;
+mod epub;
+mod error;
+mod extract;
+mod filter;
+mod limits;
+mod listing;
+mod markdown;
+mod options;
+mod pool;
+mod sink;
+mod sniff;
+mod source;
+mod template;
+mod workdir;
+
+pub use config::{parse_size, Config};
+pub use epub::EpubMetadata;
+pub use error::MyError;
+pub use extract::{Document, Extractor, Report};
+pub use filter::EntryFilter;
+pub use listing::{EntryInfo, Summary};
+pub use options::Options;
+pub use sniff::Sniffer;
+pub use workdir::WorkDir;
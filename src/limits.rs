@@ -0,0 +1,325 @@
+//! Protection against zip bombs and other resource exhaustion.
+//!
+//! Declared sizes can not be trusted, so besides checking them up front every
+//! entry is read through a [`LimitedReader`] which counts the bytes actually
+//! produced by the decompressor.
+
+use crate::source::Entry;
+use crate::{MyError, Options};
+use std::cell::Cell;
+use std::io::Read;
+
+/// Entries are always allowed to inflate to this size, whatever their ratio.
+const RATIO_ALLOWANCE: u64 = 1 << 20;
+
+/// Error carried through `std::io::Error` when a reader hits a limit.
+#[derive(Debug)]
+pub(crate) struct LimitExceeded(pub String);
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Resources left for a single run. A limit of `0` means unlimited.
+pub(crate) struct Budget {
+    max_total_size: u64,
+    max_entry_size: u64,
+    max_entries: u64,
+    max_ratio: u64,
+    total_size: Cell<u64>,
+    entries: Cell<u64>,
+    exhausted: Cell<bool>,
+}
+
+impl Budget {
+    pub fn new(opts: &Options) -> Self {
+        Self {
+            max_total_size: opts.max_total_size,
+            max_entry_size: opts.max_entry_size,
+            max_entries: opts.max_entries,
+            max_ratio: opts.max_ratio,
+            total_size: Cell::new(0),
+            entries: Cell::new(0),
+            exhausted: Cell::new(false),
+        }
+    }
+
+    /// Count the entry and check its declared sizes.
+    ///
+    /// Returns `Ok(false)` for every entry after the run wide limits were
+    /// already reported once.
+    pub fn admit(&self, entry: &Entry) -> Result<bool, MyError> {
+        if self.exhausted.get() {
+            return Ok(false);
+        }
+        self.entries.set(self.entries.get() + 1);
+        if exceeds(self.entries.get(), self.max_entries) {
+            self.exhausted.set(true);
+            return Err(MyError::Limit(format!(
+                "More than {} entries, ignoring the rest of the input",
+                self.max_entries
+            )));
+        }
+        if let Some(size) = entry.size {
+            if exceeds(size, self.max_entry_size) {
+                return Err(MyError::Limit(format!(
+                    "{}: declared size {} exceeds the entry size limit of {} bytes",
+                    entry.name, size, self.max_entry_size
+                )));
+            }
+            if let Some(compressed) = entry.compressed_size {
+                if exceeds(size, self.ratio_cap(compressed)) {
+                    return Err(MyError::Limit(format!(
+                        "{}: compression ratio exceeds the limit of {}",
+                        entry.name, self.max_ratio
+                    )));
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Wrap the content of an entry so reading it past any limit fails.
+    pub fn reader<'a>(
+        &'a self,
+        name: &str,
+        compressed_size: Option<u64>,
+        inner: &'a mut dyn Read,
+    ) -> LimitedReader<'a> {
+        LimitedReader {
+            budget: self,
+            inner,
+            name: name.to_string(),
+            compressed_size,
+            read: 0,
+        }
+    }
+
+    fn ratio_cap(&self, compressed: u64) -> u64 {
+        if self.max_ratio == 0 {
+            return 0;
+        }
+        compressed
+            .saturating_mul(self.max_ratio)
+            .max(RATIO_ALLOWANCE)
+    }
+}
+
+/// Reader failing with [`LimitExceeded`] once an entry or the whole run read
+/// more than allowed.
+pub(crate) struct LimitedReader<'a> {
+    budget: &'a Budget,
+    inner: &'a mut dyn Read,
+    name: String,
+    compressed_size: Option<u64>,
+    read: u64,
+}
+
+impl Read for LimitedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        let budget = self.budget;
+        budget.total_size.set(budget.total_size.get() + n as u64);
+        let exceeded = if exceeds(self.read, budget.max_entry_size) {
+            Some(format!(
+                "{}: exceeds the entry size limit of {} bytes",
+                self.name, budget.max_entry_size
+            ))
+        } else if self
+            .compressed_size
+            .is_some_and(|c| exceeds(self.read, budget.ratio_cap(c)))
+        {
+            Some(format!(
+                "{}: compression ratio exceeds the limit of {}",
+                self.name, budget.max_ratio
+            ))
+        } else if exceeds(budget.total_size.get(), budget.max_total_size) {
+            budget.exhausted.set(true);
+            Some(format!(
+                "{}: input exceeds the total size limit of {} bytes, ignoring the rest of it",
+                self.name, budget.max_total_size
+            ))
+        } else {
+            None
+        };
+        match exceeded {
+            Some(msg) => Err(std::io::Error::other(LimitExceeded(msg))),
+            None => Ok(n),
+        }
+    }
+}
+
+/// Reader failing with [`LimitExceeded`] once more than `max_size` bytes were
+/// read from a whole decompressed stream. Unlike [`LimitedReader`] this also
+/// counts what the archive format reads besides the entry contents.
+pub(crate) struct StreamLimit<R> {
+    inner: R,
+    max_size: u64,
+    read: u64,
+}
+
+impl<R> StreamLimit<R> {
+    pub fn new(inner: R, max_size: u64) -> Self {
+        Self {
+            inner,
+            max_size,
+            read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for StreamLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        if exceeds(self.read, self.max_size) {
+            return Err(std::io::Error::other(LimitExceeded(format!(
+                "Decompressed input exceeds the total size limit of {} bytes",
+                self.max_size
+            ))));
+        }
+        Ok(n)
+    }
+}
+
+fn exceeds(value: u64, limit: u64) -> bool {
+    limit != 0 && value > limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Extractor;
+    use std::io::Write;
+
+    fn admit(
+        budget: &Budget,
+        size: Option<u64>,
+        compressed_size: Option<u64>,
+    ) -> Result<bool, MyError> {
+        budget.admit(&Entry {
+            name: "page.html".into(),
+            path: None,
+            is_dir: false,
+            size,
+            compressed_size,
+            method: None,
+            url: None,
+            date: None,
+            mtime: None,
+            content: &mut std::io::empty(),
+        })
+    }
+
+    /// Read `len` bytes of an entry stored in `compressed_size` bytes.
+    fn read(budget: &Budget, len: u64, compressed_size: Option<u64>) -> std::io::Result<u64> {
+        let mut content = std::io::repeat(b'x').take(len);
+        let mut reader = budget.reader("page.html", compressed_size, &mut content);
+        std::io::copy(&mut reader, &mut std::io::sink())
+    }
+
+    #[test]
+    fn declared_sizes_are_checked_up_front() {
+        let budget = Budget::new(&Options::new().set_max_entry_size(100));
+        assert!(matches!(admit(&budget, Some(100), None), Ok(true)));
+        assert!(matches!(admit(&budget, None, None), Ok(true)));
+        match admit(&budget, Some(101), None) {
+            Err(MyError::Limit(msg)) => assert!(msg.contains("declared size 101"), "{}", msg),
+            res => panic!("unexpected {:?}", res),
+        }
+        assert!(read(&budget, 100, None).is_ok());
+        assert!(read(&budget, 101, None).is_err());
+    }
+
+    #[test]
+    fn ratio_applies_after_the_allowance() {
+        let budget = Budget::new(&Options::new().set_max_ratio(10));
+        assert!(matches!(
+            admit(&budget, Some(RATIO_ALLOWANCE), Some(1)),
+            Ok(true)
+        ));
+        assert!(admit(&budget, Some(RATIO_ALLOWANCE + 1), Some(1)).is_err());
+        let compressed = RATIO_ALLOWANCE;
+        assert!(matches!(
+            admit(&budget, Some(compressed * 10), Some(compressed)),
+            Ok(true)
+        ));
+        assert!(admit(&budget, Some(compressed * 10 + 1), Some(compressed)).is_err());
+
+        assert!(read(&budget, RATIO_ALLOWANCE, Some(1)).is_ok());
+        let err = read(&budget, RATIO_ALLOWANCE + 1, Some(1)).unwrap_err();
+        assert!(err.to_string().contains("compression ratio"), "{}", err);
+        // Stored entries are not compressed at all.
+        assert!(read(&budget, RATIO_ALLOWANCE + 1, None).is_ok());
+    }
+
+    #[test]
+    fn total_size_ends_the_run() {
+        let budget = Budget::new(&Options::new().set_max_total_size(100));
+        assert!(matches!(admit(&budget, Some(60), None), Ok(true)));
+        assert!(read(&budget, 60, None).is_ok());
+        assert!(matches!(admit(&budget, Some(60), None), Ok(true)));
+        let err = read(&budget, 60, None).unwrap_err();
+        assert!(err.to_string().contains("total size limit"), "{}", err);
+        assert!(matches!(admit(&budget, Some(1), None), Ok(false)));
+    }
+
+    #[test]
+    fn stream_limit_counts_everything_read() {
+        let mut stream = StreamLimit::new(std::io::repeat(b'x').take(100), 100);
+        assert_eq!(
+            std::io::copy(&mut stream, &mut std::io::sink()).unwrap(),
+            100
+        );
+        let mut stream = StreamLimit::new(std::io::repeat(b'x').take(101), 100);
+        let err = MyError::from(std::io::copy(&mut stream, &mut std::io::sink()).unwrap_err());
+        assert!(matches!(err, MyError::Limit(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn no_limits() {
+        let opts = Options::new()
+            .set_max_total_size(0)
+            .set_max_entry_size(0)
+            .set_max_entries(0)
+            .set_max_ratio(0);
+        let budget = Budget::new(&opts);
+        assert!(matches!(admit(&budget, Some(u64::MAX), Some(1)), Ok(true)));
+        assert!(read(&budget, 4 * RATIO_ALLOWANCE, Some(1)).is_ok());
+    }
+
+    #[test]
+    fn entries_over_a_limit_are_skipped_if_asked_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&input).unwrap());
+        let stored =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("small.html", stored).unwrap();
+        zip.write_all(b"<p>small</p>").unwrap();
+        zip.start_file("big.html", stored).unwrap();
+        zip.write_all(format!("<p>{}</p>", "big ".repeat(50)).as_bytes())
+            .unwrap();
+        zip.finish().unwrap();
+        let opts = Options::new()
+            .set_input_file(input.to_string_lossy())
+            .set_max_entry_size(100);
+
+        let res = Extractor::new(opts.clone()).documents();
+        assert!(matches!(res, Err(MyError::Limit(_))), "{:?}", res);
+
+        let (documents, report) = Extractor::new(opts.set_skip_over_limit(true))
+            .documents()
+            .unwrap();
+        let names = documents.iter().map(|d| &d.name[..]).collect::<Vec<_>>();
+        assert_eq!(names, ["small.html"]);
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.skipped[0].to_string().contains("big.html"));
+        assert!(report.failures.is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+//! What an extraction would do with the entries of an input, found without
+//! rendering or copying anything.
+
+use crate::epub::EpubMetadata;
+use crate::sink::Sink;
+use crate::{Document, MyError};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// An entry of the input and what becomes of it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EntryInfo {
+    /// Name of the entry inside the input.
+    pub name: String,
+    /// Relative path the entry would be extracted to, `None` if its name would
+    /// escape the output directory.
+    pub path: Option<PathBuf>,
+    /// Uncompressed size as declared by the input.
+    pub size: Option<u64>,
+    /// Size of the entry as stored in the input, if it is compressed.
+    pub compressed_size: Option<u64>,
+    /// Compression method of the entry, e.g. `Deflated`.
+    pub method: Option<String>,
+    /// What the entry was detected as: `dir`, `html`, an archive format such
+    /// as `zip`, `tar` or `warc`, `other`, or `unknown` if it was skipped
+    /// before looking at its content.
+    pub kind: &'static str,
+    /// What extracting does with it: render it to `text`, `copy` it to the
+    /// rest directory, create it as a `dir`, descend into it as a `nested`
+    /// archive, or `skip` it.
+    pub action: &'static str,
+}
+
+/// Statistics of an input as a whole.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Summary {
+    /// Format of the input, e.g. `zip`, `tar.gz` or `directory`.
+    pub format: String,
+    /// Title, authors and language, for an EPUB publication.
+    pub metadata: Option<EpubMetadata>,
+    /// Number of entries, including those of nested archives.
+    pub entries: u64,
+    pub dirs: u64,
+    /// Entries rendered to text.
+    pub text: u64,
+    /// Entries copied to the rest directory.
+    pub copied: u64,
+    pub nested: u64,
+    pub skipped: u64,
+    /// Sum of the declared uncompressed sizes.
+    pub size: u64,
+    /// Sum of the stored sizes, counting uncompressed entries with their size.
+    pub stored_size: u64,
+}
+
+impl Summary {
+    /// Count `entry` in.
+    pub(crate) fn add(&mut self, entry: &EntryInfo) {
+        self.entries += 1;
+        match entry.action {
+            "dir" => self.dirs += 1,
+            "text" => self.text += 1,
+            "copy" => self.copied += 1,
+            "nested" => self.nested += 1,
+            _ => self.skipped += 1,
+        }
+        // Nested archives are counted through their entries.
+        if entry.action != "nested" {
+            let size = entry.size.unwrap_or(0);
+            self.size += size;
+            self.stored_size += entry.compressed_size.unwrap_or(size);
+        }
+    }
+}
+
+/// Collects the entries of the input without processing them.
+#[derive(Default)]
+pub(crate) struct ListSink {
+    pub entries: Vec<EntryInfo>,
+    pub format: String,
+    pub metadata: Option<EpubMetadata>,
+}
+
+impl Sink for ListSink {
+    fn input(&mut self, format: &str) -> Result<(), MyError> {
+        // Only the outermost input, not the nested archives.
+        if self.format.is_empty() {
+            self.format = format.to_string();
+        }
+        Ok(())
+    }
+
+    fn metadata(&mut self, metadata: &EpubMetadata) -> Result<(), MyError> {
+        self.metadata.get_or_insert_with(|| metadata.clone());
+        Ok(())
+    }
+
+    fn listed(&mut self, entry: EntryInfo) -> bool {
+        self.entries.push(entry);
+        false
+    }
+
+    fn html(&mut self, _: Document) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    fn other(&mut self, _: &Path, _: &mut dyn Read) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    fn dir(&mut self, _: &Path) -> Result<(), MyError> {
+        Ok(())
+    }
+}
+
+/// Renders the one entry called `name`, passing over all others.
+pub(crate) struct FindSink {
+    pub name: String,
+    /// The entry, once it was seen.
+    pub found: Option<EntryInfo>,
+    pub document: Option<Document>,
+}
+
+impl Sink for FindSink {
+    fn listed(&mut self, entry: EntryInfo) -> bool {
+        if entry.name != self.name {
+            return false;
+        }
+        let render = entry.action == "text";
+        self.found = Some(entry);
+        render
+    }
+
+    fn html(&mut self, document: Document) -> Result<(), MyError> {
+        self.document = Some(document);
+        Ok(())
+    }
+
+    fn other(&mut self, _: &Path, _: &mut dyn Read) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    fn dir(&mut self, _: &Path) -> Result<(), MyError> {
+        Ok(())
+    }
+}
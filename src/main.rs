@@ -1,183 +1,35 @@
 use hp::{Parser, Template};
-use html2text::render::text_renderer::{PlainDecorator, RichDecorator, TrivialDecorator};
-use std::io::Write;
-use zip::read::ZipArchive;
+use rusty_html_extractor::{parse_size, Config, EntryInfo, Extractor, MyError, Report, Summary};
 
-const EXTRACT_PATH: &'static str = "/tmp/rusty-html-extractor/";
-
-#[derive(Clone, PartialEq, Eq)]
-struct Options {
-    width: u32,
-    file_artifacts: bool,
-    output_format: String,
-    output_text_file: String,
-    output_dir: String,
-    input_file: String,
-}
-
-impl Default for Options {
-    fn default() -> Self {
-        Self {
-            width: 80,
-            file_artifacts: false,
-            output_format: String::from("trivial"),
-            output_text_file: String::from("./html_text.txt"),
-            output_dir: String::from("./rest"),
-            input_file: String::from(""),
-        }
-    }
-}
-
-impl Options {
-    fn new() -> Self {
-        Default::default()
-    }
-
-    fn set_width(mut self, width: u32) -> Self {
-        self.width = width;
-        self
-    }
-
-    fn set_artifacts(mut self, a: bool) -> Self {
-        self.file_artifacts = a;
-        self
-    }
-
-    fn set_format(mut self, fmt: impl AsRef<str>) -> Self {
-        self.output_format = fmt.as_ref().into();
-        self
-    }
-}
-
-#[derive(Debug)]
-enum MyError {
-    Io(std::io::Error),
-    Msg(&'static str),
-}
-
-impl From<zip::result::ZipError> for MyError {
-    fn from(_: zip::result::ZipError) -> Self {
-        Self::Msg("Zip error occured")
-    }
-}
-
-impl From<&'static str> for MyError {
-    fn from(value: &'static str) -> Self {
-        Self::Msg(value)
-    }
-}
-
-impl From<std::io::Error> for MyError {
-    fn from(value: std::io::Error) -> Self {
-        Self::Io(value)
-    }
-}
-
-impl From<std::num::ParseIntError> for MyError {
-    fn from(_: std::num::ParseIntError) -> Self {
-        Self::Msg("Not a number.")
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("Error: {}", error);
+        std::process::exit(error.exit_code());
     }
 }
 
-impl std::fmt::Display for MyError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Io(e) => write!(f, "{}", e),
-            Self::Msg(e) => write!(f, "{}", e),
-        }
-    }
-}
-
-fn parse(opts: Options) -> Result<(), MyError> {
-    if !std::path::PathBuf::from(opts.input_file.clone()).exists() {
-        return Err("Input file does not exist".into());
-    }
-    let file = std::fs::File::open(opts.input_file)?;
-    let mut archive = ZipArchive::new(file)?;
-    archive.extract(std::path::PathBuf::from(&EXTRACT_PATH))?;
-    let mut output_text = std::fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&opts.output_text_file)?;
-    std::fs::create_dir_all(opts.output_dir.clone())?;
-    let output_dir = std::path::PathBuf::from(opts.output_dir);
-    for i in 0..archive.len() {
-        let mut archive_file = archive.by_index(i)?;
-        let name = archive_file.name().to_string();
-        let fname = match archive_file.enclosed_name() {
-            Some(p) => p,
-            None => continue,
-        };
-
-        if fname.to_str().unwrap().ends_with('/') {
-            let new_path = output_dir.clone().join(fname);
-            std::fs::create_dir_all(new_path)?;
-        } else if String::from_utf8(
-            std::process::Command::new("file")
-                .arg(std::path::PathBuf::from(EXTRACT_PATH).join(&name))
-                .output()?
-                .stdout,
-        )
-        .unwrap_or("file".into())
-        .to_lowercase()
-        .contains("html document")
-        {
-            opts.file_artifacts
-                .then(|| {
-                    output_text
-                        .write(format!("# begin {}\n", name).as_bytes())
-                        .ok()
-                })
-                .flatten();
-            let html_text = match &opts.output_format[..] {
-                "trivial" => html2text::from_read_with_decorator(
-                    archive_file,
-                    opts.width.try_into().unwrap_or(80),
-                    TrivialDecorator::new(),
-                ),
-                "plain" => html2text::from_read_with_decorator(
-                    archive_file,
-                    opts.width.try_into().unwrap_or(80),
-                    PlainDecorator::new(),
-                ),
-                "rich" => html2text::from_read_with_decorator(
-                    archive_file,
-                    opts.width.try_into().unwrap_or(80),
-                    RichDecorator::new(),
-                ),
-                _ => unreachable!(),
-            };
-            let txt = html_text
-                .lines()
-                .filter(|l| !l.is_empty())
-                .map(ToString::to_string)
-                .collect::<Vec<String>>();
-            output_text.write_all(txt.join("\n").as_bytes())?;
-            opts.file_artifacts
-                .then(|| {
-                    output_text
-                        .write(format!("# end {}\n", name).as_bytes())
-                        .ok()
-                })
-                .flatten();
-        } else {
-            let mut outfile = std::fs::File::create(output_dir.clone().join(fname))?;
-            std::io::copy(&mut archive_file, &mut outfile)?;
-        }
-    }
-    Ok(())
-}
-
-fn main() -> Result<(), MyError> {
+fn run() -> Result<(), MyError> {
     let mut parser = Parser::new()
-        .with_description("Extract text from zipped html files.")
+        .with_description(
+            "Extract text from html files in ZIP, tar and EPUB archives or directories. \
+             Commands: `extract`(default) writes the text and the rest of the files, `list` \
+             shows what would become of every entry, `inspect` sums up the input and `cat \
+             ENTRY` renders a single HTML entry to standard output. \
+             Exits with 2 for invalid arguments, 3 for an unreadable input, 4 for a corrupt \
+             input, 5 when an output can not be written and 6 when entries failed with \
+             --keep-going. Defaults are read from ~/.config/rusty-html-extractor/config.toml, \
+             the nearest .rusty-html-extractor.toml and RHE_* environment variables, e.g. \
+             RHE_WIDTH=100.",
+        )
         .exit_on_help(true);
     let input = parser.add_template(
         Template::new()
             .matches("-i")
             .matches("--input")
-            .with_help("Input file.")
+            .with_help(
+                "Input archive (zip, tar, tar.gz, tar.zst, tar.xz, tar.bz2, epub), directory, \
+                 HTML file or MHTML or WARC web archive.",
+            )
             .optional_values(false)
             .number_of_values(1),
     );
@@ -185,7 +37,7 @@ fn main() -> Result<(), MyError> {
         Template::new()
             .matches("-o")
             .matches("--output")
-            .with_help("Two output files. First is the path to the extracted html, second is the directory where the rest of the files will be stored. Defaults are `html_text.txt` and `rest/`")
+            .with_help("Two output files. First is the path to the extracted html, second is the directory where the rest of the files will be stored. Defaults are `html_text.txt` and `rest/`. A `-` text file writes to standard output, a `-` directory drops the rest of the files.")
             .number_of_values(2)
             .optional_values(false),
     );
@@ -202,26 +54,284 @@ fn main() -> Result<(), MyError> {
         .matches("--artifacts")
         .number_of_values(0)
         .optional_values(true)
-        .with_help("This will insert an information about what file the given snippet originated from in the output text file."));
+        .with_help("This will insert an information about what file the given snippet originated from in the output text file, same as `--preset markers`."));
     let ff = parser.add_template(
         Template::new()
             .matches("-f")
             .matches("--format")
             .number_of_values(1)
             .optional_values(true)
-            .with_help("Given one of `plain`, `trivial`(default), `rich`, format the html in the given format."),
+            .with_help("Given one of `plain`, `trivial`(default), `rich`, `markdown`, format the html in the given format. `json` and `jsonl` write one record per document."),
+    );
+    let html_glob = parser.add_template(
+        Template::new()
+            .matches("--html")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help(
+                "Comma separated glob patterns of entries which are always treated as HTML.",
+            ),
+    );
+    let other_glob = parser.add_template(
+        Template::new()
+            .matches("--not-html")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Comma separated glob patterns of entries which are never treated as HTML."),
+    );
+    let kt = parser.add_template(
+        Template::new()
+            .matches("--keep-temp")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Do not remove the per-run working directory, useful for debugging."),
+    );
+    let ep = parser.add_template(
+        Template::new()
+            .matches("--epub")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Treat the input as an EPUB and output its chapters in reading order. EPUB files are detected automatically."),
+    );
+    let rc = parser.add_template(
+        Template::new()
+            .matches("-r")
+            .matches("--recursive")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Descend into archives found inside the input, their entries are named `outer.zip!/inner.html`."),
+    );
+    let md = parser.add_template(
+        Template::new()
+            .matches("--max-depth")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("How many levels of nested archives to descend into with `--recursive`, defaults to 8. Deeper archives are an error, or skipped with `--skip-over-limit`."),
+    );
+    let ml = parser.add_template(
+        Template::new()
+            .matches("--links")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Link style of the `markdown` format, `inline`(default) or `reference`."),
+    );
+    let mt = parser.add_template(
+        Template::new()
+            .matches("--tables")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help(
+                "Table style of the `markdown` format, `gfm`(default) pipe tables or raw `html`.",
+            ),
+    );
+    let ws = parser.add_template(
+        Template::new()
+            .matches("--whitespace")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help(
+                "Blank lines of the text, `preserve` them, `collapse`(default) runs of them into one or `strip` them all.",
+            ),
+    );
+    let pr = parser.add_template(
+        Template::new()
+            .matches("--preset")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help(
+                "What surrounds the documents of the text output: `blank`(default) lines between them, `markers` naming their entry, `form-feed` characters between them, `yaml` front matter or `none`.",
+            ),
+    );
+    let hd = parser.add_template(
+        Template::new()
+            .matches("--header")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help(
+                "Template of the header of every document, with placeholders `{path}`, `{title}`, `{index}`, `{size}`, `{charset}`, `{mtime}`, `{url}`, `{date}`, `{crc32}` and `{words}`, `{title:json}` quotes the value. Escapes `\\n`, `\\t` and `\\f` are understood.",
+            ),
+    );
+    let ft = parser.add_template(
+        Template::new()
+            .matches("--footer")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Template of the footer of every document, like `--header`."),
+    );
+    let sr = parser.add_template(
+        Template::new()
+            .matches("--separator")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Text written between two documents, e.g. `\\n` for a blank line."),
+    );
+    let sp = parser.add_template(
+        Template::new()
+            .matches("-s")
+            .matches("--split")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Write the text of every html file into its own file under the given directory, mirroring the archive tree. `page.html` becomes `page.txt`, `page.md` or `page.json` depending on the format."),
+    );
+    let ie = parser.add_template(
+        Template::new()
+            .matches("--input-encoding")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Decode the html files with the given encoding, e.g. `windows-1252`, instead of detecting it."),
+    );
+    let mc = parser.add_template(
+        Template::new()
+            .matches("-m")
+            .matches("--main-content")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Keep only the main content of every page, dropping navigation, banners, footers and sidebars."),
+    );
+    let sel = parser.add_template(
+        Template::new()
+            .matches("--select")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Render only the elements matching the given CSS selectors, e.g. `article, main, #content`."),
+    );
+    let exc = parser.add_template(
+        Template::new()
+            .matches("--exclude")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Drop the elements matching the given CSS selectors before rendering, e.g. `nav, .ads, script`."),
+    );
+    let inc = parser.add_template(
+        Template::new()
+            .matches("--include")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Comma separated glob patterns of the entries to process, e.g. `docs/**/*.html,!**/_static/**`. Patterns starting with `!` skip the entries they match. Nested archives are only descended into if they are included themselves, e.g. `*.html,*.zip`."),
+    );
+    let cp = parser.add_template(
+        Template::new()
+            .matches("--copy")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Comma separated glob patterns of the non-html entries copied into the output directory. All of them are copied by default."),
+    );
+    let mts = parser.add_template(
+        Template::new()
+            .matches("--max-total-size")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Maximum number of bytes extracted from the whole input, e.g. `8G`(default). `0` disables the limit."),
+    );
+    let mes = parser.add_template(
+        Template::new()
+            .matches("--max-entry-size")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Maximum uncompressed size of a single entry, e.g. `1G`(default). `0` disables the limit."),
+    );
+    let mne = parser.add_template(
+        Template::new()
+            .matches("--max-entries")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Maximum number of entries, defaults to 100000. `0` disables the limit."),
+    );
+    let mr = parser.add_template(
+        Template::new()
+            .matches("--max-ratio")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Maximum compression ratio of a single entry, defaults to 200. `0` disables the limit."),
+    );
+    let sol = parser.add_template(
+        Template::new()
+            .matches("--skip-over-limit")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Report and skip entries going over a limit instead of aborting."),
+    );
+    let jobs = parser.add_template(
+        Template::new()
+            .matches("-j")
+            .matches("--jobs")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help(
+                "Number of threads rendering HTML entries, defaults to 1. `0` uses one per CPU.",
+            ),
+    );
+    let kg = parser.add_template(
+        Template::new()
+            .matches("-k")
+            .matches("--keep-going")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Go on after entries which fail and list them at the end, exiting with 6."),
     );
 
+    let extract = parser.add_template(
+        Template::new()
+            .matches("extract")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Write the text of the HTML entries and copy the rest, the default."),
+    );
+    let list = parser.add_template(
+        Template::new()
+            .matches("list")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("List the entries with their size, compression, detected type and whether they become text or are copied."),
+    );
+    let inspect = parser.add_template(
+        Template::new()
+            .matches("inspect")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help("Show the format, entry counts and sizes of the input."),
+    );
+    let cat = parser.add_template(
+        Template::new()
+            .matches("cat")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Render the HTML entry with the given name to standard output."),
+    );
+    let pf = parser.add_template(
+        Template::new()
+            .matches("-p")
+            .matches("--profile")
+            .number_of_values(1)
+            .optional_values(true)
+            .with_help("Apply the settings of a profile defined in the configuration files, the other flags still override them."),
+    );
+    let pc = parser.add_template(
+        Template::new()
+            .matches("--print-config")
+            .number_of_values(0)
+            .optional_values(true)
+            .with_help(
+                "Print the effective configuration, including the other flags given, and exit.",
+            ),
+    );
     let res = parser.parse(None);
     match res {
         Ok(pargs) => {
-            if pargs.has_with_id(input) {
-                let mut opts: Options = Options::new();
-                opts.input_file = pargs.get_with_id(input).unwrap().values()[0].clone();
+            if pargs.has_with_id(input) || pargs.has_with_id(pc) {
+                let mut config = Config::load()?;
+                if pargs.has_with_id(pf) {
+                    config.select(&pargs.get_with_id(pf).unwrap().values()[0])?;
+                }
+                let mut opts = config.options()?;
+                if pargs.has_with_id(input) {
+                    opts = opts.set_input_file(&pargs.get_with_id(input).unwrap().values()[0]);
+                }
                 if pargs.has_with_id(output) {
                     let output_files = pargs.get_with_id(output).unwrap();
-                    opts.output_text_file = output_files.values()[0].clone();
-                    opts.output_dir = output_files.values()[1].clone();
+                    opts = opts
+                        .set_output_text_file(&output_files.values()[0])
+                        .set_output_dir(&output_files.values()[1]);
                 }
                 if pargs.has_with_id(wh) {
                     opts = opts.set_width(pargs.get_with_id(wh).unwrap().values()[0].parse()?)
@@ -230,21 +340,207 @@ fn main() -> Result<(), MyError> {
                     opts = opts.set_artifacts(true);
                 }
                 if pargs.has_with_id(ff) {
-                    let str = pargs.get_with_id(ff).unwrap().values()[0].clone();
-                    match &str[..] {
-                        "plain" => opts = opts.set_format("plain"),
-                        "trivial" => opts = opts.set_format("trivial"),
-                        "rich" => opts = opts.set_format("rich"),
-                        _ => return Err("Unrecognized format.".into()),
-                    };
-                }
-                if let Err(error) = parse(opts) {
-                    println!("ERROR OCCURED: {}", error);
-                }
-                std::fs::remove_dir_all(std::path::PathBuf::from(&EXTRACT_PATH))?;
+                    opts = opts.set_format(&pargs.get_with_id(ff).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(html_glob) {
+                    opts = opts.set_html_patterns(split_list(
+                        &pargs.get_with_id(html_glob).unwrap().values()[0],
+                    ));
+                }
+                if pargs.has_with_id(other_glob) {
+                    opts = opts.set_other_patterns(split_list(
+                        &pargs.get_with_id(other_glob).unwrap().values()[0],
+                    ));
+                }
+                if pargs.has_with_id(kt) {
+                    opts = opts.set_keep_temp(true);
+                }
+                if pargs.has_with_id(ep) {
+                    opts = opts.set_epub(true);
+                }
+                if pargs.has_with_id(rc) {
+                    opts = opts.set_recursive(true);
+                }
+                if pargs.has_with_id(md) {
+                    opts = opts.set_max_depth(pargs.get_with_id(md).unwrap().values()[0].parse()?);
+                }
+                if pargs.has_with_id(ml) {
+                    opts = opts.set_markdown_links(&pargs.get_with_id(ml).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(mt) {
+                    opts = opts.set_markdown_tables(&pargs.get_with_id(mt).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(ws) {
+                    opts = opts.set_whitespace(&pargs.get_with_id(ws).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(pr) {
+                    opts = opts.set_preset(&pargs.get_with_id(pr).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(hd) {
+                    opts = opts.set_header(&pargs.get_with_id(hd).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(ft) {
+                    opts = opts.set_footer(&pargs.get_with_id(ft).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(sr) {
+                    opts = opts.set_separator(&pargs.get_with_id(sr).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(sp) {
+                    opts = opts.set_split_dir(&pargs.get_with_id(sp).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(ie) {
+                    opts = opts.set_input_encoding(&pargs.get_with_id(ie).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(mc) {
+                    opts = opts.set_main_content(true);
+                }
+                if pargs.has_with_id(sel) {
+                    opts = opts.set_select(&pargs.get_with_id(sel).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(exc) {
+                    opts = opts.set_exclude(&pargs.get_with_id(exc).unwrap().values()[0]);
+                }
+                if pargs.has_with_id(inc) {
+                    opts = opts.set_include_patterns(split_list(
+                        &pargs.get_with_id(inc).unwrap().values()[0],
+                    ));
+                }
+                if pargs.has_with_id(cp) {
+                    opts = opts
+                        .set_copy_patterns(split_list(&pargs.get_with_id(cp).unwrap().values()[0]));
+                }
+                if pargs.has_with_id(mts) {
+                    opts = opts.set_max_total_size(parse_size(
+                        &pargs.get_with_id(mts).unwrap().values()[0],
+                    )?);
+                }
+                if pargs.has_with_id(mes) {
+                    opts = opts.set_max_entry_size(parse_size(
+                        &pargs.get_with_id(mes).unwrap().values()[0],
+                    )?);
+                }
+                if pargs.has_with_id(mne) {
+                    opts =
+                        opts.set_max_entries(pargs.get_with_id(mne).unwrap().values()[0].parse()?);
+                }
+                if pargs.has_with_id(mr) {
+                    opts = opts.set_max_ratio(pargs.get_with_id(mr).unwrap().values()[0].parse()?);
+                }
+                if pargs.has_with_id(sol) {
+                    opts = opts.set_skip_over_limit(true);
+                }
+                if pargs.has_with_id(jobs) {
+                    opts = opts.set_jobs(pargs.get_with_id(jobs).unwrap().values()[0].parse()?);
+                }
+                if pargs.has_with_id(kg) {
+                    opts = opts.set_keep_going(true);
+                }
+                if pargs.has_with_id(pc) {
+                    print!("{}", config.dump(&opts));
+                    return Ok(());
+                }
+                let commands = [extract, list, inspect, cat];
+                if commands.iter().filter(|c| pargs.has_with_id(**c)).count() > 1 {
+                    return Err(MyError::Usage(
+                        "Give only one of extract, list, inspect and cat.".into(),
+                    ));
+                }
+                let extractor = Extractor::new(opts);
+                if pargs.has_with_id(list) {
+                    let (entries, report) = extractor.entries()?;
+                    print_entries(&entries);
+                    finish(report)
+                } else if pargs.has_with_id(inspect) {
+                    let (summary, report) = extractor.inspect()?;
+                    print_summary(&summary);
+                    finish(report)
+                } else if pargs.has_with_id(cat) {
+                    let name = &pargs.get_with_id(cat).unwrap().values()[0];
+                    print!("{}", extractor.find(name)?.text);
+                    Ok(())
+                } else {
+                    finish(extractor.run()?)
+                }
+            } else {
+                Err(MyError::Usage("No input given, see --help.".into()))
             }
         }
-        Err(e) => println!("{e}"),
+        Err(e) => Err(MyError::Usage(e.to_string())),
+    }
+}
+
+/// Tell about the skipped entries and a kept working directory, and fail if
+/// some entries failed in keep-going mode.
+fn finish(report: Report) -> Result<(), MyError> {
+    for skipped in &report.skipped {
+        eprintln!("Skipping {}", skipped);
+    }
+    if let Some(work_dir) = &report.work_dir {
+        eprintln!("Keeping working directory {}", work_dir.display());
+    }
+    if report.failures.is_empty() {
+        Ok(())
+    } else {
+        Err(MyError::Failures(report.failures))
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn print_entries(entries: &[EntryInfo]) {
+    let size = |size: Option<u64>| size.map_or("-".to_string(), |s| s.to_string());
+    println!(
+        "{:>10} {:>10} {:<9} {:<6} {:<6} name",
+        "size", "stored", "method", "kind", "action"
+    );
+    for entry in entries {
+        println!(
+            "{:>10} {:>10} {:<9} {:<6} {:<6} {}",
+            size(entry.size),
+            size(entry.compressed_size),
+            entry.method.as_deref().unwrap_or("-"),
+            entry.kind,
+            entry.action,
+            entry.name
+        );
+    }
+}
+
+fn print_summary(summary: &Summary) {
+    // One column more than the longest label, `  skipped:`.
+    let field = |label: &str, value: &dyn std::fmt::Display| {
+        println!("{:<11}{}", format!("{}:", label), value);
+    };
+    field("format", &summary.format);
+    if let Some(metadata) = &summary.metadata {
+        if let Some(title) = &metadata.title {
+            field("title", title);
+        }
+        if !metadata.authors.is_empty() {
+            field("authors", &metadata.authors.join(", "));
+        }
+        if let Some(language) = &metadata.language {
+            field("language", language);
+        }
+    }
+    field("entries", &summary.entries);
+    field("  text", &summary.text);
+    field("  copied", &summary.copied);
+    field("  dirs", &summary.dirs);
+    field("  nested", &summary.nested);
+    field("  skipped", &summary.skipped);
+    field("size", &format!("{} bytes", summary.size));
+    let mut stored = format!("{} bytes", summary.stored_size);
+    if summary.stored_size > 0 && summary.stored_size < summary.size {
+        let ratio = summary.size as f64 / summary.stored_size as f64;
+        stored.push_str(&format!(", ratio {:.1}", ratio));
     }
-    Ok(())
+    field("stored", &stored);
 }
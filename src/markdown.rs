@@ -0,0 +1,449 @@
+//! CommonMark/GFM rendering of HTML documents.
+//!
+//! Unlike the html2text decorators this keeps the structure of the document:
+//! headings, lists, links, code blocks and tables come out as Markdown.
+
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Html, Node};
+
+/// Tags whose content never ends up in the output.
+const SKIPPED: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "iframe", "object", "svg",
+];
+
+/// Tags which start a new block in the output.
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "ul",
+];
+
+/// Render `html` as Markdown.
+///
+/// `links` is either `inline` or `reference`, `tables` either `gfm` for pipe
+/// tables or `html` to keep tables as raw HTML blocks.
+pub(crate) fn render(html: &str, links: &str, tables: &str) -> String {
+    let document = Html::parse_document(html);
+    let mut renderer = Renderer {
+        reference_links: links == "reference",
+        html_tables: tables == "html",
+        references: Vec::new(),
+    };
+    let mut out = renderer.blocks(document.tree.root()).join("\n\n");
+    if !renderer.references.is_empty() {
+        out.push_str("\n\n");
+        for (i, url) in renderer.references.iter().enumerate() {
+            out.push_str(&format!("[{}]: {}\n", i + 1, destination(url)));
+        }
+    }
+    let out = out.trim_end();
+    if out.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", out)
+    }
+}
+
+struct Renderer {
+    reference_links: bool,
+    html_tables: bool,
+    references: Vec<String>,
+}
+
+impl Renderer {
+    /// Render the children of `node` as a list of blocks.
+    fn blocks(&mut self, node: NodeRef<Node>) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut inline = String::new();
+        for child in node.children() {
+            match child.value() {
+                Node::Element(e) if BLOCKS.contains(&e.name()) => {
+                    push_paragraph(&mut blocks, &mut inline);
+                    let block = self.block(child, e.name());
+                    if !block.trim().is_empty() {
+                        blocks.push(block);
+                    }
+                }
+                _ => inline.push_str(&self.inline(child)),
+            }
+        }
+        push_paragraph(&mut blocks, &mut inline);
+        blocks
+    }
+
+    fn block(&mut self, node: NodeRef<Node>, name: &str) -> String {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(1);
+                format!(
+                    "{} {}",
+                    "#".repeat(level),
+                    self.inline_children(node).trim()
+                )
+            }
+            "p" | "dt" | "summary" | "figcaption" => {
+                escape_line_starts(self.inline_children(node).trim())
+            }
+            "hr" => "---".to_string(),
+            "pre" => code_block(node),
+            "blockquote" => prefix_lines(&self.blocks(node).join("\n\n"), "> ", "> "),
+            "ul" | "ol" => self.list(node, name == "ol"),
+            "table" if self.html_tables => {
+                ElementRef::wrap(node).map(|e| e.html()).unwrap_or_default()
+            }
+            "table" => self.table(node),
+            _ => self.blocks(node).join("\n\n"),
+        }
+    }
+
+    fn list(&mut self, node: NodeRef<Node>, ordered: bool) -> String {
+        let start = element(node)
+            .and_then(|e| e.attr("start"))
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(1);
+        let mut items = Vec::new();
+        for item in node.children().filter(|c| is_element(*c, "li")) {
+            let marker = if ordered {
+                format!("{}. ", start + items.len())
+            } else {
+                "- ".to_string()
+            };
+            let indent = " ".repeat(marker.len());
+            // Blocks of an item need a blank line between them, or a second
+            // paragraph would continue the first one.
+            let mut content = String::new();
+            for block in self.blocks(item) {
+                if !content.is_empty() {
+                    content.push_str(if is_list(&block) { "\n" } else { "\n\n" });
+                }
+                content.push_str(&block);
+            }
+            items.push(prefix_lines(&content, &marker, &indent));
+        }
+        items.join("\n")
+    }
+
+    fn table(&mut self, node: NodeRef<Node>) -> String {
+        let rows = node
+            .descendants()
+            .filter(|n| is_element(*n, "tr"))
+            .map(|row| {
+                row.children()
+                    .filter(|c| is_element(*c, "td") || is_element(*c, "th"))
+                    .map(|cell| {
+                        self.inline_children(cell)
+                            .split_whitespace()
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                            .replace('|', "\\|")
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return String::new();
+        }
+        let line = |cells: &[String]| {
+            let mut cells = cells.to_vec();
+            cells.resize(columns, String::new());
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut out = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+        out.extend(rows[1..].iter().map(|r| line(r)));
+        out.join("\n")
+    }
+
+    fn inline_children(&mut self, node: NodeRef<Node>) -> String {
+        node.children().map(|c| self.inline(c)).collect()
+    }
+
+    fn inline(&mut self, node: NodeRef<Node>) -> String {
+        let e = match node.value() {
+            Node::Text(t) => return escape(&collapse_whitespace(t)),
+            Node::Element(e) => e,
+            _ => return String::new(),
+        };
+        if SKIPPED.contains(&e.name()) {
+            return String::new();
+        }
+        if BLOCKS.contains(&e.name()) {
+            return format!(" {} ", self.blocks(node).join(" "));
+        }
+        match e.name() {
+            "br" => "\\\n".to_string(),
+            "strong" | "b" => wrap(&self.inline_children(node), "**"),
+            "em" | "i" => wrap(&self.inline_children(node), "*"),
+            "del" | "s" | "strike" => wrap(&self.inline_children(node), "~~"),
+            "code" | "kbd" | "samp" => inline_code(&text_content(node)),
+            "img" => {
+                let alt = e.attr("alt").unwrap_or_default();
+                match e.attr("src") {
+                    Some(src) => format!("![{}]({})", escape(alt), destination(src)),
+                    None => String::new(),
+                }
+            }
+            "a" => {
+                let text = self.inline_children(node);
+                match e.attr("href") {
+                    Some(href) if !href.starts_with('#') && !text.trim().is_empty() => {
+                        if self.reference_links {
+                            self.references.push(href.to_string());
+                            format!("[{}][{}]", text.trim(), self.references.len())
+                        } else {
+                            format!("[{}]({})", text.trim(), destination(href))
+                        }
+                    }
+                    _ => text,
+                }
+            }
+            _ => self.inline_children(node),
+        }
+    }
+}
+
+fn push_paragraph(blocks: &mut Vec<String>, inline: &mut String) {
+    let paragraph = inline.lines().map(str::trim).collect::<Vec<_>>().join("\n");
+    if !paragraph.trim().is_empty() {
+        blocks.push(escape_line_starts(paragraph.trim()));
+    }
+    inline.clear();
+}
+
+fn code_block(node: NodeRef<Node>) -> String {
+    let language = std::iter::once(node)
+        .chain(node.children().filter(|c| is_element(*c, "code")))
+        .filter_map(element)
+        .filter_map(|e| e.attr("class"))
+        .flat_map(str::split_whitespace)
+        .find_map(|c| c.strip_prefix("language-"))
+        .unwrap_or_default();
+    let code = text_content(node);
+    let fence = if code.contains("```") { "~~~~" } else { "```" };
+    format!(
+        "{}{}\n{}\n{}",
+        fence,
+        language,
+        code.trim_end_matches('\n'),
+        fence
+    )
+}
+
+fn inline_code(code: &str) -> String {
+    let code = collapse_whitespace(code);
+    if code.trim().is_empty() {
+        return String::new();
+    }
+    if code.contains('`') {
+        format!("`` {} ``", code.trim())
+    } else {
+        format!("`{}`", code.trim())
+    }
+}
+
+fn wrap(text: &str, marker: &str) -> String {
+    if text.trim().is_empty() {
+        text.to_string()
+    } else {
+        format!("{}{}{}", marker, text.trim(), marker)
+    }
+}
+
+/// Prefix the first line with `first` and all the following ones with `rest`.
+fn prefix_lines(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !space {
+                out.push(' ');
+            }
+            space = true;
+        } else {
+            out.push(c);
+            space = false;
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Escape what would start another block at the beginning of a line of
+/// paragraph text: list markers, setext heading underlines and fences.
+fn escape_line_starts(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+            if line.starts_with(['-', '+', '=', '~']) {
+                format!("\\{}", line)
+            } else if (1..=9).contains(&digits) && line[digits..].starts_with(['.', ')']) {
+                format!("{}\\{}", &line[..digits], &line[digits..])
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Does the block rendered by [`Renderer::list`] start a list? Paragraphs
+/// starting like one are escaped.
+fn is_list(block: &str) -> bool {
+    let digits = block.bytes().take_while(u8::is_ascii_digit).count();
+    block.starts_with("- ") || digits > 0 && block[digits..].starts_with(". ")
+}
+
+/// Link destination of `url`, in angle brackets if it has spaces or
+/// parentheses which would end it early.
+fn destination(url: &str) -> String {
+    if url.contains(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '<' | '>')) {
+        let url = collapse_whitespace(url)
+            .replace('<', "%3C")
+            .replace('>', "%3E");
+        format!("<{}>", url.trim())
+    } else {
+        url.to_string()
+    }
+}
+
+fn text_content(node: NodeRef<Node>) -> String {
+    node.descendants()
+        .filter_map(|n| match n.value() {
+            Node::Text(t) => Some(&**t),
+            _ => None,
+        })
+        .collect()
+}
+
+fn element<'a>(node: NodeRef<'a, Node>) -> Option<&'a scraper::node::Element> {
+    node.value().as_element()
+}
+
+fn is_element(node: NodeRef<Node>, name: &str) -> bool {
+    element(node).map(|e| e.name() == name).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(body: &str) -> String {
+        render(
+            &format!("<html><body>{}</body></html>", body),
+            "inline",
+            "gfm",
+        )
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            markdown("<p>a *b* [c] &lt;d&gt; #e</p>"),
+            "a \\*b\\* \\[c\\] \\<d\\> \\#e\n"
+        );
+        assert_eq!(
+            markdown("<p>1. one<br>- two<br>+ three<br>2) four</p><p>===</p>"),
+            "1\\. one\\\n\\- two\\\n\\+ three\\\n2\\) four\n\n\\===\n"
+        );
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            markdown(r#"<p><a href="a.html">A</a> <a href="u v(1)">B</a></p>"#),
+            "[A](a.html) [B](<u v(1)>)\n"
+        );
+        assert_eq!(
+            markdown(r#"<p><img src="a b.png" alt="[x]"></p>"#),
+            "![\\[x\\]](<a b.png>)\n"
+        );
+        let html = r#"<p><a href="a.html">A</a> <a href="b c.html">B</a></p>"#;
+        assert_eq!(
+            render(html, "reference", "gfm"),
+            "[A][1] [B][2]\n\n[1]: a.html\n[2]: <b c.html>\n"
+        );
+    }
+
+    #[test]
+    fn nested_lists() {
+        assert_eq!(
+            markdown("<ol><li>one<ul><li>sub</li></ul></li><li><p>p1</p><p>p2</p></li></ol>"),
+            "1. one\n   - sub\n2. p1\n\n   p2\n"
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let table = "<table><tr><th>a</th><th>b</th></tr><tr><td>1</td><td>x|y</td></tr></table>";
+        assert_eq!(markdown(table), "| a | b |\n| --- | --- |\n| 1 | x\\|y |\n");
+        assert!(render(table, "inline", "html").starts_with("<table>"));
+    }
+
+    #[test]
+    fn code_fences() {
+        assert_eq!(
+            markdown("<pre><code class=\"language-rust\">fn main() {}\n</code></pre>"),
+            "```rust\nfn main() {}\n```\n"
+        );
+        assert_eq!(markdown("<pre>a\n```\nb</pre>"), "~~~~\na\n```\nb\n~~~~\n");
+        assert_eq!(markdown("<p>use <code>a`b</code></p>"), "use `` a`b ``\n");
+    }
+}
@@ -0,0 +1,416 @@
+use crate::MyError;
+
+/// Path standing for standard input or output, or for no rest directory.
+pub(crate) const STDIO: &str = "-";
+
+// Values of the settings taking one of a few, checked before a run.
+pub(crate) const FORMATS: &[&str] = &["plain", "trivial", "rich", "markdown", "json", "jsonl"];
+pub(crate) const LINK_STYLES: &[&str] = &["inline", "reference"];
+pub(crate) const TABLE_STYLES: &[&str] = &["gfm", "html"];
+pub(crate) const WHITESPACE_POLICIES: &[&str] = &["preserve", "collapse", "strip"];
+pub(crate) const PRESETS: &[&str] = &["blank", "markers", "form-feed", "yaml", "none"];
+
+/// Settings controlling how an archive is turned into text.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Options {
+    pub(crate) width: u32,
+    pub(crate) file_artifacts: bool,
+    pub(crate) output_format: String,
+    pub(crate) output_text_file: String,
+    pub(crate) output_dir: String,
+    pub(crate) input_file: String,
+    pub(crate) html_patterns: Vec<String>,
+    pub(crate) other_patterns: Vec<String>,
+    pub(crate) keep_temp: bool,
+    pub(crate) epub: bool,
+    pub(crate) recursive: bool,
+    pub(crate) max_depth: u32,
+    pub(crate) markdown_links: String,
+    pub(crate) markdown_tables: String,
+    pub(crate) split_dir: Option<String>,
+    pub(crate) input_encoding: Option<String>,
+    pub(crate) main_content: bool,
+    pub(crate) select: Option<String>,
+    pub(crate) exclude: Option<String>,
+    pub(crate) include_patterns: Vec<String>,
+    pub(crate) copy_patterns: Vec<String>,
+    pub(crate) max_total_size: u64,
+    pub(crate) max_entry_size: u64,
+    pub(crate) max_entries: u64,
+    pub(crate) max_ratio: u64,
+    pub(crate) skip_over_limit: bool,
+    pub(crate) jobs: usize,
+    pub(crate) keep_going: bool,
+    pub(crate) whitespace: String,
+    pub(crate) preset: Option<String>,
+    pub(crate) header: Option<String>,
+    pub(crate) footer: Option<String>,
+    pub(crate) separator: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            file_artifacts: false,
+            output_format: String::from("trivial"),
+            output_text_file: String::from("./html_text.txt"),
+            output_dir: String::from("./rest"),
+            input_file: String::from(""),
+            html_patterns: Vec::new(),
+            other_patterns: Vec::new(),
+            keep_temp: false,
+            epub: false,
+            recursive: false,
+            max_depth: 8,
+            markdown_links: String::from("inline"),
+            markdown_tables: String::from("gfm"),
+            split_dir: None,
+            input_encoding: None,
+            main_content: false,
+            select: None,
+            exclude: None,
+            include_patterns: Vec::new(),
+            copy_patterns: Vec::new(),
+            max_total_size: 8 << 30,
+            max_entry_size: 1 << 30,
+            max_entries: 100_000,
+            max_ratio: 200,
+            skip_over_limit: false,
+            jobs: 1,
+            keep_going: false,
+            whitespace: String::from("collapse"),
+            preset: None,
+            header: None,
+            footer: None,
+            separator: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Wrap the rendered text at the given column.
+    pub fn set_width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Surround every document with `# begin`/`# end` markers naming its source entry.
+    pub fn set_artifacts(mut self, a: bool) -> Self {
+        self.file_artifacts = a;
+        self
+    }
+
+    /// One of `trivial`, `plain`, `rich`, `markdown`, `json` or `jsonl`.
+    ///
+    /// The JSON formats write one record per document, with the text rendered
+    /// as with `trivial`.
+    ///
+    /// Like the other settings taking one of a few values, anything else fails
+    /// the run with [`MyError::Usage`].
+    pub fn set_format(mut self, fmt: impl AsRef<str>) -> Self {
+        self.output_format = fmt.as_ref().into();
+        self
+    }
+
+    /// Path of the archive or directory to read, `-` for standard input.
+    pub fn set_input_file(mut self, path: impl AsRef<str>) -> Self {
+        self.input_file = path.as_ref().into();
+        self
+    }
+
+    /// Path of the file the combined text is written to, `-` for standard
+    /// output.
+    pub fn set_output_text_file(mut self, path: impl AsRef<str>) -> Self {
+        self.output_text_file = path.as_ref().into();
+        self
+    }
+
+    /// Directory where the non-HTML entries are copied to, `-` to not write
+    /// them anywhere.
+    pub fn set_output_dir(mut self, path: impl AsRef<str>) -> Self {
+        self.output_dir = path.as_ref().into();
+        self
+    }
+
+    /// Glob patterns of entries which are always rendered as HTML, regardless
+    /// of what their content looks like.
+    pub fn set_html_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.html_patterns = patterns;
+        self
+    }
+
+    /// Glob patterns of entries which are never rendered as HTML.
+    pub fn set_other_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.other_patterns = patterns;
+        self
+    }
+
+    /// Do not remove the per-run working directory once done.
+    pub fn set_keep_temp(mut self, keep: bool) -> Self {
+        self.keep_temp = keep;
+        self
+    }
+
+    /// Treat the input as an EPUB even if it does not declare itself as one.
+    pub fn set_epub(mut self, epub: bool) -> Self {
+        self.epub = epub;
+        self
+    }
+
+    /// Descend into ZIP, tar and EPUB archives found inside the input.
+    pub fn set_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// How many levels of nested archives are descended into when recursive,
+    /// archives nested deeper fail with [`MyError::Limit`].
+    pub fn set_max_depth(mut self, depth: u32) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// How the `markdown` format writes links, either `inline` or `reference`.
+    pub fn set_markdown_links(mut self, style: impl AsRef<str>) -> Self {
+        self.markdown_links = style.as_ref().into();
+        self
+    }
+
+    /// How the `markdown` format writes tables, either `gfm` pipe tables or
+    /// raw `html`.
+    pub fn set_markdown_tables(mut self, style: impl AsRef<str>) -> Self {
+        self.markdown_tables = style.as_ref().into();
+        self
+    }
+
+    /// Write every document into its own file under `dir`, mirroring the
+    /// archive tree, instead of a single output text file. Documents which
+    /// would end up in the same file are numbered, as in `index-1.txt`.
+    pub fn set_split_dir(mut self, dir: impl AsRef<str>) -> Self {
+        self.split_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Write the text into the single output text file again.
+    pub fn clear_split_dir(mut self) -> Self {
+        self.split_dir = None;
+        self
+    }
+
+    /// Decode every HTML entry with the given encoding label instead of
+    /// detecting it, e.g. `windows-1252` or `shift_jis`.
+    pub fn set_input_encoding(mut self, label: impl AsRef<str>) -> Self {
+        self.input_encoding = Some(label.as_ref().into());
+        self
+    }
+
+    /// Detect the encoding of every HTML entry again.
+    pub fn clear_input_encoding(mut self) -> Self {
+        self.input_encoding = None;
+        self
+    }
+
+    /// Keep only the main content of every page, dropping navigation,
+    /// banners, footers and sidebars before rendering.
+    pub fn set_main_content(mut self, main_content: bool) -> Self {
+        self.main_content = main_content;
+        self
+    }
+
+    /// Render only the elements matching the CSS selector list, e.g.
+    /// `article, main, #content`.
+    pub fn set_select(mut self, css: impl AsRef<str>) -> Self {
+        self.select = Some(css.as_ref().into());
+        self
+    }
+
+    /// Render the whole document again.
+    pub fn clear_select(mut self) -> Self {
+        self.select = None;
+        self
+    }
+
+    /// Drop the elements matching the CSS selector list before rendering, e.g.
+    /// `nav, .ads, [aria-hidden=true]`.
+    pub fn set_exclude(mut self, css: impl AsRef<str>) -> Self {
+        self.exclude = Some(css.as_ref().into());
+        self
+    }
+
+    /// Drop nothing before rendering again.
+    pub fn clear_exclude(mut self) -> Self {
+        self.exclude = None;
+        self
+    }
+
+    /// Glob patterns of the entries which are processed at all, patterns
+    /// starting with `!` skip the entries they match. With `recursive` a
+    /// nested archive is only descended into if it is included itself.
+    pub fn set_include_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.include_patterns = patterns;
+        self
+    }
+
+    /// Glob patterns of the non-HTML entries which are copied into the output
+    /// directory, all of them are copied if empty.
+    pub fn set_copy_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.copy_patterns = patterns;
+        self
+    }
+
+    /// Maximum number of bytes read from all entries together, `0` for no limit.
+    pub fn set_max_total_size(mut self, bytes: u64) -> Self {
+        self.max_total_size = bytes;
+        self
+    }
+
+    /// Maximum uncompressed size of a single entry, `0` for no limit.
+    pub fn set_max_entry_size(mut self, bytes: u64) -> Self {
+        self.max_entry_size = bytes;
+        self
+    }
+
+    /// Maximum number of entries, `0` for no limit.
+    pub fn set_max_entries(mut self, entries: u64) -> Self {
+        self.max_entries = entries;
+        self
+    }
+
+    /// Maximum ratio between the uncompressed and compressed size of an
+    /// entry, `0` for no limit.
+    pub fn set_max_ratio(mut self, ratio: u64) -> Self {
+        self.max_ratio = ratio;
+        self
+    }
+
+    /// Report and skip entries going over a limit instead of aborting.
+    pub fn set_skip_over_limit(mut self, skip: bool) -> Self {
+        self.skip_over_limit = skip;
+        self
+    }
+
+    /// Number of threads rendering documents, `0` for one per CPU.
+    pub fn set_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Record entries which fail and go on with the rest of the input,
+    /// instead of aborting at the first failure.
+    pub fn set_keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Blank lines of the rendered text are kept as they are with `preserve`,
+    /// runs of them become a single one with `collapse`(default) and `strip`
+    /// removes them all. Preformatted text and code blocks are left alone.
+    pub fn set_whitespace(mut self, policy: impl AsRef<str>) -> Self {
+        self.whitespace = policy.as_ref().into();
+        self
+    }
+
+    /// Header, footer and separator of the documents in the text output:
+    /// `blank` lines between them (default), `markers` like the artifacts,
+    /// `form-feed` characters between them, `yaml` front matter or `none`.
+    pub fn set_preset(mut self, preset: impl AsRef<str>) -> Self {
+        self.preset = Some(preset.as_ref().into());
+        self
+    }
+
+    /// Go back to the default preset.
+    pub fn clear_preset(mut self) -> Self {
+        self.preset = None;
+        self
+    }
+
+    /// Template of the line(s) written before every document, replacing the
+    /// one of the preset. Placeholders such as `{path}`, `{title}`, `{index}`,
+    /// `{size}`, `{charset}` and `{mtime}` are replaced by the values of the
+    /// document.
+    pub fn set_header(mut self, template: impl AsRef<str>) -> Self {
+        self.header = Some(template.as_ref().into());
+        self
+    }
+
+    /// Use the header of the preset again.
+    pub fn clear_header(mut self) -> Self {
+        self.header = None;
+        self
+    }
+
+    /// Template of the line(s) written after every document, like the header.
+    pub fn set_footer(mut self, template: impl AsRef<str>) -> Self {
+        self.footer = Some(template.as_ref().into());
+        self
+    }
+
+    /// Use the footer of the preset again.
+    pub fn clear_footer(mut self) -> Self {
+        self.footer = None;
+        self
+    }
+
+    /// Text written between two documents, replacing the one of the preset.
+    pub fn set_separator(mut self, separator: impl AsRef<str>) -> Self {
+        self.separator = Some(separator.as_ref().into());
+        self
+    }
+
+    /// Use the separator of the preset again.
+    pub fn clear_separator(mut self) -> Self {
+        self.separator = None;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn artifacts(&self) -> bool {
+        self.file_artifacts
+    }
+
+    pub fn format(&self) -> &str {
+        &self.output_format
+    }
+
+    pub fn input_file(&self) -> &str {
+        &self.input_file
+    }
+
+    pub fn output_text_file(&self) -> &str {
+        &self.output_text_file
+    }
+
+    pub fn output_dir(&self) -> &str {
+        &self.output_dir
+    }
+
+    /// Fail on the settings taking one of a few values which got another.
+    pub(crate) fn check(&self) -> Result<(), MyError> {
+        let settings = [
+            ("format", &self.output_format, FORMATS),
+            ("link style", &self.markdown_links, LINK_STYLES),
+            ("table style", &self.markdown_tables, TABLE_STYLES),
+            ("whitespace policy", &self.whitespace, WHITESPACE_POLICIES),
+        ];
+        let preset = self.preset.iter().map(|preset| ("preset", preset, PRESETS));
+        for (setting, value, allowed) in settings.into_iter().chain(preset) {
+            if !allowed.contains(&&value[..]) {
+                return Err(MyError::Usage(format!(
+                    "Unknown {} {}, expected one of {}",
+                    setting,
+                    value,
+                    allowed.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+}
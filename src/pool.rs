@@ -0,0 +1,230 @@
+//! Rendering of HTML documents on a pool of worker threads.
+//!
+//! Entries are still read one after the other, only the CPU bound decoding
+//! and rendering of the HTML runs in parallel. Rendered documents are handed
+//! to the sink in the order they were submitted, so the output does not
+//! depend on the number of workers.
+
+use crate::{Document, MyError};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Mutex;
+
+/// An HTML entry waiting to be rendered.
+pub(crate) struct Job {
+    pub name: String,
+    pub path: PathBuf,
+    pub raw: Vec<u8>,
+    pub url: Option<String>,
+    pub date: Option<String>,
+    pub mtime: Option<String>,
+}
+
+/// Receives the rendered documents, or the errors rendering them, in order.
+pub(crate) type Emit<'a> = dyn FnMut(Result<Document, MyError>) -> Result<(), MyError> + 'a;
+
+/// Submits jobs to the workers and passes their documents on in order.
+pub(crate) struct Pool {
+    jobs: SyncSender<(usize, Job)>,
+    done: Receiver<(usize, Result<Document, MyError>)>,
+    submitted: Cell<usize>,
+    emitted: Cell<usize>,
+    /// Most jobs submitted but not emitted yet.
+    ahead: usize,
+    pending: RefCell<BTreeMap<usize, Result<Document, MyError>>>,
+}
+
+/// Run `f` with a pool of `workers` threads turning jobs into documents with
+/// `render`. The workers are joined before returning.
+pub(crate) fn with_pool<T>(
+    workers: usize,
+    render: &(dyn Fn(Job) -> Result<Document, MyError> + Sync),
+    f: impl FnOnce(&Pool) -> T,
+) -> T {
+    // The queue only holds a few jobs, but documents finished early still
+    // wait in `pending` for a slow one before them. [`Pool::submit`] bounds
+    // both by waiting once `ahead` jobs are not emitted yet.
+    let (jobs, queue) = mpsc::sync_channel::<(usize, Job)>(workers * 2);
+    let (results, done) = mpsc::channel();
+    let queue = Mutex::new(queue);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let queue = &queue;
+            let results = results.clone();
+            scope.spawn(move || loop {
+                let received = queue.lock().map(|q| q.recv());
+                let (index, job) = match received {
+                    Ok(Ok(next)) => next,
+                    _ => break,
+                };
+                if results.send((index, caught(render, job))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(results);
+        // Dropping the pool at the end of `f` closes the queue and stops the
+        // workers.
+        f(&Pool {
+            jobs,
+            done,
+            submitted: Cell::new(0),
+            emitted: Cell::new(0),
+            ahead: workers * 4,
+            pending: RefCell::new(BTreeMap::new()),
+        })
+    })
+}
+
+impl Pool {
+    /// Queue a job, then pass on the documents which are ready and next in
+    /// line.
+    pub fn submit(&self, job: Job, emit: &mut Emit) -> Result<(), MyError> {
+        while self.submitted.get() - self.emitted.get() >= self.ahead {
+            self.receive(emit)?;
+        }
+        self.jobs
+            .send((self.submitted.get(), job))
+            .map_err(|_| stopped())?;
+        self.submitted.set(self.submitted.get() + 1);
+        while let Ok((index, document)) = self.done.try_recv() {
+            self.pending.borrow_mut().insert(index, document);
+        }
+        self.flush(emit)
+    }
+
+    /// Wait for all submitted jobs and pass on the remaining documents.
+    pub fn finish(&self, emit: &mut Emit) -> Result<(), MyError> {
+        while self.emitted.get() < self.submitted.get() {
+            self.receive(emit)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the next document to be rendered and pass on the ones ready.
+    fn receive(&self, emit: &mut Emit) -> Result<(), MyError> {
+        let (index, document) = self.done.recv().map_err(|_| stopped())?;
+        self.pending.borrow_mut().insert(index, document);
+        self.flush(emit)
+    }
+
+    fn flush(&self, emit: &mut Emit) -> Result<(), MyError> {
+        loop {
+            let document = self.pending.borrow_mut().remove(&self.emitted.get());
+            match document {
+                Some(document) => {
+                    self.emitted.set(self.emitted.get() + 1);
+                    emit(document)?;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Run `render` on `job`, turning a panic into an error of the entry. A
+/// worker dying silently would leave [`Pool::finish`] waiting for its
+/// document forever.
+pub(crate) fn caught(
+    render: impl FnOnce(Job) -> Result<Document, MyError>,
+    job: Job,
+) -> Result<Document, MyError> {
+    let (name, path) = (job.name.clone(), job.path.clone());
+    std::panic::catch_unwind(AssertUnwindSafe(|| render(job))).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|m| m.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        let error = MyError::Corrupt(format!("Rendering failed: {}", message));
+        Err(error.in_entry(&name, Some(&path)))
+    })
+}
+
+fn stopped() -> MyError {
+    MyError::Io(std::io::Error::other(
+        "Rendering thread stopped unexpectedly",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(name: &str) -> Job {
+        Job {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            raw: Vec::new(),
+            url: None,
+            date: None,
+            mtime: None,
+        }
+    }
+
+    fn document(job: Job) -> Result<Document, MyError> {
+        if job.name == "bad.html" {
+            panic!("broken renderer");
+        }
+        if job.name == "slow.html" {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        Ok(Document {
+            name: job.name,
+            path: job.path,
+            text: String::new(),
+            title: None,
+            charset: "UTF-8".into(),
+            size: 0,
+            crc32: 0,
+            url: None,
+            date: None,
+            mtime: None,
+        })
+    }
+
+    #[test]
+    fn panicking_render_fails_its_entry_only() {
+        let mut names = Vec::new();
+        let mut errors = Vec::new();
+        let res = with_pool(2, &document, |pool| {
+            let mut emit = |document: Result<Document, MyError>| {
+                match document {
+                    Ok(document) => names.push(document.name),
+                    Err(error) => errors.push(error.to_string()),
+                }
+                Ok(())
+            };
+            for name in ["a.html", "bad.html", "c.html"] {
+                pool.submit(job(name), &mut emit)?;
+            }
+            pool.finish(&mut emit)
+        });
+        assert!(res.is_ok());
+        assert_eq!(names, ["a.html", "c.html"]);
+        assert_eq!(errors, ["bad.html: Rendering failed: broken renderer"]);
+    }
+
+    #[test]
+    fn slow_render_holds_up_reading() {
+        let mut emitted = 0;
+        let res = with_pool(2, &document, |pool| {
+            let mut emit = |_| {
+                emitted += 1;
+                Ok(())
+            };
+            pool.submit(job("slow.html"), &mut emit)?;
+            for i in 0..100 {
+                pool.submit(job(&format!("{}.html", i)), &mut emit)?;
+                assert!(pool.submitted.get() - pool.emitted.get() <= pool.ahead);
+                assert!(pool.pending.borrow().len() <= pool.ahead);
+            }
+            pool.finish(&mut emit)
+        });
+        assert!(res.is_ok());
+        assert_eq!(emitted, 101);
+    }
+}
@@ -0,0 +1,286 @@
+//! Destinations for the results of an extraction.
+
+use crate::epub::EpubMetadata;
+use crate::error::Context;
+use crate::listing::EntryInfo;
+use crate::options::STDIO;
+use crate::source::unique_name;
+use crate::template::Frame;
+use crate::{Document, MyError, Options};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Receives the entries of an archive as they are processed.
+pub(crate) trait Sink {
+    /// Format of the input, e.g. `zip`, reported first and again for every
+    /// nested archive.
+    fn input(&mut self, _format: &str) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    /// Publication metadata, reported before any document.
+    fn metadata(&mut self, _metadata: &EpubMetadata) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    /// What becomes of an entry, reported before its content is read any
+    /// further. Returns whether to go on and render, copy or create it,
+    /// nested archives are descended into either way.
+    fn listed(&mut self, _entry: EntryInfo) -> bool {
+        true
+    }
+
+    /// The rendered text of an HTML entry.
+    fn html(&mut self, document: Document) -> Result<(), MyError>;
+
+    /// Content of an entry which is not HTML.
+    fn other(&mut self, path: &Path, content: &mut dyn Read) -> Result<(), MyError>;
+
+    /// A directory entry.
+    fn dir(&mut self, path: &Path) -> Result<(), MyError>;
+
+    /// Called once all entries were processed.
+    fn finish(&mut self) -> Result<(), MyError> {
+        Ok(())
+    }
+}
+
+/// Collects the rendered documents in memory, ignoring everything else.
+#[derive(Default)]
+pub(crate) struct MemorySink {
+    pub metadata: Option<EpubMetadata>,
+    pub documents: Vec<Document>,
+}
+
+impl Sink for MemorySink {
+    fn metadata(&mut self, metadata: &EpubMetadata) -> Result<(), MyError> {
+        self.metadata = Some(metadata.clone());
+        Ok(())
+    }
+
+    fn html(&mut self, document: Document) -> Result<(), MyError> {
+        self.documents.push(document);
+        Ok(())
+    }
+
+    fn other(&mut self, _: &Path, _: &mut dyn Read) -> Result<(), MyError> {
+        Ok(())
+    }
+
+    fn dir(&mut self, _: &Path) -> Result<(), MyError> {
+        Ok(())
+    }
+}
+
+/// Writes the text into a single file and copies the rest into a directory.
+///
+/// With the `json` and `jsonl` formats the text file holds one record per
+/// document instead of the plain text. In split mode every document is written
+/// to its own file, mirroring the archive tree under the split directory.
+pub(crate) struct FileSink<W: Write> {
+    output_text: W,
+    /// Where the text ends up, for error messages.
+    text_path: PathBuf,
+    /// `None` when the rest of the entries are not written anywhere.
+    output_dir: Option<PathBuf>,
+    split_dir: Option<PathBuf>,
+    /// Files written to the split directory so far, relative to it.
+    split_names: HashSet<String>,
+    frame: Frame,
+    format: String,
+    records: usize,
+}
+
+impl<W: Write> FileSink<W> {
+    pub fn new(output_text: W, opts: &Options, frame: Frame) -> Self {
+        Self {
+            output_text,
+            text_path: PathBuf::from(&opts.output_text_file),
+            output_dir: (opts.output_dir != STDIO).then(|| PathBuf::from(&opts.output_dir)),
+            split_dir: opts.split_dir.as_ref().map(PathBuf::from),
+            split_names: HashSet::new(),
+            frame,
+            format: opts.output_format.clone(),
+            records: 0,
+        }
+    }
+}
+
+impl<W: Write> Sink for FileSink<W> {
+    fn metadata(&mut self, metadata: &EpubMetadata) -> Result<(), MyError> {
+        if self.split_dir.is_none() && self.format != "json" && self.format != "jsonl" {
+            let res = self.output_text.write_all(metadata.header().as_bytes());
+            res.writing(&self.text_path)?;
+        }
+        Ok(())
+    }
+
+    fn html(&mut self, document: Document) -> Result<(), MyError> {
+        if let Some(split_dir) = &self.split_dir {
+            // Entries such as index.html and index.htm end up with the same
+            // name once the extension is replaced.
+            let name = document.path.with_extension(self.extension());
+            let name = unique_name(name.to_string_lossy().into_owned(), &mut self.split_names);
+            let path = split_dir.join(name);
+            let res = self.split_document(&path, &document);
+            res.writing(&path)?;
+            self.records += 1;
+            return Ok(());
+        }
+        let res = self.text_document(&document);
+        res.writing(&self.text_path)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    fn other(&mut self, path: &Path, content: &mut dyn Read) -> Result<(), MyError> {
+        let path = match &self.output_dir {
+            Some(output_dir) => output_dir.join(path),
+            None => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).writing(parent)?;
+        }
+        let mut outfile = std::fs::File::create(&path).writing(&path)?;
+        if let Err(e) = copy(content, &mut outfile, &path) {
+            // Do not leave a truncated file behind, e.g. after hitting a limit.
+            drop(outfile);
+            std::fs::remove_file(&path).writing(&path)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn dir(&mut self, path: &Path) -> Result<(), MyError> {
+        if let Some(output_dir) = &self.output_dir {
+            let path = output_dir.join(path);
+            std::fs::create_dir_all(&path).writing(&path)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), MyError> {
+        if self.format == "json" && self.split_dir.is_none() {
+            let end = if self.records == 0 { "[]\n" } else { "\n]\n" };
+            let res = self.output_text.write_all(end.as_bytes());
+            res.writing(&self.text_path)?;
+        }
+        let res = self.output_text.flush();
+        res.writing(&self.text_path)
+    }
+}
+
+impl<W: Write> FileSink<W> {
+    /// Append a document to the text output.
+    fn text_document(&mut self, document: &Document) -> Result<(), MyError> {
+        if self.format == "json" {
+            let separator = if self.records == 0 { "[\n" } else { ",\n" };
+            self.output_text.write_all(separator.as_bytes())?;
+            serde_json::to_writer_pretty(&mut self.output_text, &record(document))?;
+            return Ok(());
+        }
+        if self.records > 0 && self.format != "jsonl" {
+            self.output_text
+                .write_all(self.frame.separator().as_bytes())?;
+        }
+        let index = self.records + 1;
+        write_document(
+            &mut self.output_text,
+            document,
+            &self.format,
+            &self.frame,
+            index,
+        )
+    }
+
+    /// Write a document to its own file in split mode.
+    fn split_document(&self, path: &Path, document: &Document) -> Result<(), MyError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        if self.format == "json" {
+            serde_json::to_writer_pretty(&mut file, &record(document))?;
+            file.write_all(b"\n")?;
+        } else {
+            let index = self.records + 1;
+            write_document(&mut file, document, &self.format, &self.frame, index)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Extension of the per document files in split mode.
+    fn extension(&self) -> &'static str {
+        match &self.format[..] {
+            "markdown" => "md",
+            "json" | "jsonl" => "json",
+            _ => "txt",
+        }
+    }
+}
+
+/// Write the `index`th document as plain text between the header and footer of
+/// `frame`, or as a JSON line. Without a header template, its capture URL and
+/// date come first if it has them.
+fn write_document(
+    out: &mut impl Write,
+    document: &Document,
+    format: &str,
+    frame: &Frame,
+    index: usize,
+) -> Result<(), MyError> {
+    if format == "jsonl" {
+        serde_json::to_writer(&mut *out, &record(document))?;
+        out.write_all(b"\n")?;
+        return Ok(());
+    }
+    if let Some(header) = frame.header(document, index) {
+        out.write_all(header.as_bytes())?;
+    }
+    if !frame.has_header() {
+        if let Some(url) = &document.url {
+            out.write_all(format!("# url: {}\n", url).as_bytes())?;
+        }
+        if let Some(date) = &document.date {
+            out.write_all(format!("# date: {}\n", date).as_bytes())?;
+        }
+    }
+    out.write_all(document.text.as_bytes())?;
+    if let Some(footer) = frame.footer(document, index) {
+        out.write_all(footer.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// JSON record describing a single document.
+fn record(document: &Document) -> serde_json::Value {
+    serde_json::json!({
+        "path": document.name,
+        "title": document.title,
+        "charset": document.charset,
+        "size": document.size,
+        "crc32": format!("{:08x}", document.crc32),
+        "text": document.text,
+        "word_count": document.word_count(),
+        "url": document.url,
+        "date": document.date,
+        "mtime": document.mtime,
+    })
+}
+
+/// Copy `content` to the file at `path`. Unlike `std::io::copy` this keeps
+/// the errors reading the entry apart from those writing the output.
+fn copy(content: &mut dyn Read, out: &mut impl Write, path: &Path) -> Result<(), MyError> {
+    let mut buf = [0; 8192];
+    loop {
+        let n = match content.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        out.write_all(&buf[..n]).writing(path)?;
+    }
+}
@@ -0,0 +1,255 @@
+//! In-process detection of HTML content.
+//!
+//! The decision is made from, in order: user supplied glob overrides, the entry
+//! extension and finally a sniff of the leading bytes modeled after the WHATWG
+//! MIME sniffing algorithm, extended to recognise XHTML and HTML fragments.
+
+use crate::MyError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Number of leading bytes inspected when sniffing an entry.
+pub const SNIFF_LEN: usize = 1024;
+
+const HTML_EXTENSIONS: &[&str] = &["html", "htm", "xhtml", "xht", "shtml"];
+
+/// Tags from the WHATWG "text/html" sniffing table.
+const WHATWG_TAGS: &[&str] = &[
+    "!doctype html",
+    "html",
+    "head",
+    "script",
+    "iframe",
+    "h1",
+    "div",
+    "font",
+    "table",
+    "a",
+    "style",
+    "title",
+    "b",
+    "body",
+    "br",
+    "p",
+    "!--",
+];
+
+/// Tags which commonly start an HTML fragment without any document wrapper.
+const FRAGMENT_TAGS: &[&str] = &[
+    "article",
+    "section",
+    "main",
+    "nav",
+    "header",
+    "footer",
+    "span",
+    "ul",
+    "ol",
+    "li",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "pre",
+    "blockquote",
+    "form",
+    "img",
+    "meta",
+    "link",
+];
+
+const XHTML_NAMESPACE: &[u8] = b"http://www.w3.org/1999/xhtml";
+
+/// Decides whether an archive entry should be rendered as HTML.
+#[derive(Clone, Debug)]
+pub struct Sniffer {
+    force_html: GlobSet,
+    force_other: GlobSet,
+}
+
+impl Sniffer {
+    /// Build a sniffer from glob patterns forcing entries to be treated as HTML
+    /// or as other files. Patterns forcing HTML take precedence.
+    pub fn new(html_patterns: &[String], other_patterns: &[String]) -> Result<Self, MyError> {
+        Ok(Self {
+            force_html: glob_set(html_patterns)?,
+            force_other: glob_set(other_patterns)?,
+        })
+    }
+
+    /// Is the entry called `name`, starting with the bytes `head`, an HTML document?
+    pub fn is_html(&self, name: &str, head: &[u8]) -> bool {
+        if self.force_html.is_match(name) {
+            return true;
+        }
+        if self.force_other.is_match(name) {
+            return false;
+        }
+        let text = decode_head(head);
+        if has_html_extension(name) {
+            return !is_binary(&text);
+        }
+        sniff_html(&text)
+    }
+}
+
+pub(crate) fn glob_set(patterns: &[String]) -> Result<GlobSet, MyError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+pub(crate) fn has_html_extension(name: &str) -> bool {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| HTML_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Strip a byte order mark and, for UTF-16, keep only the low bytes so the
+/// ASCII based checks below work regardless of the encoding.
+fn decode_head(head: &[u8]) -> Vec<u8> {
+    match head {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => rest.to_vec(),
+        [0xFF, 0xFE, rest @ ..] => rest.chunks(2).map(|c| c[0]).collect(),
+        [0xFE, 0xFF, rest @ ..] => rest.chunks(2).filter_map(|c| c.get(1).copied()).collect(),
+        _ => head.to_vec(),
+    }
+}
+
+/// Binary data bytes as defined by the WHATWG sniffing algorithm.
+fn is_binary(text: &[u8]) -> bool {
+    text.iter()
+        .any(|b| matches!(b, 0x00..=0x08 | 0x0B | 0x0E..=0x1A | 0x1C..=0x1F))
+}
+
+fn sniff_html(text: &[u8]) -> bool {
+    if is_binary(text) {
+        return false;
+    }
+    let start = text
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    let text = text[start..].to_ascii_lowercase();
+    if text.starts_with(b"<?xml") {
+        return contains(&text, XHTML_NAMESPACE) || contains(&text, b"<!doctype html");
+    }
+    if WHATWG_TAGS
+        .iter()
+        .chain(FRAGMENT_TAGS)
+        .any(|tag| starts_with_tag(&text, tag))
+    {
+        return true;
+    }
+    has_charset_hint(&text)
+}
+
+fn starts_with_tag(text: &[u8], tag: &str) -> bool {
+    let tag = tag.as_bytes();
+    text.len() > tag.len() + 1
+        && text[0] == b'<'
+        && text[1..].starts_with(tag)
+        && matches!(
+            text[tag.len() + 1],
+            b' ' | b'>' | b'\t' | b'\n' | b'\r' | b'/'
+        )
+}
+
+/// A `<meta charset>` or `http-equiv` declaration only shows up in HTML.
+fn has_charset_hint(text: &[u8]) -> bool {
+    contains(text, b"<meta charset") || contains(text, b"http-equiv=\"content-type\"")
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    find(haystack, needle).is_some()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniffer() -> Sniffer {
+        Sniffer::new(&[], &[]).unwrap()
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+
+    fn utf16be(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        bytes
+    }
+
+    #[test]
+    fn documents_are_html() {
+        let sniffer = sniffer();
+        assert!(sniffer.is_html("page", b"<!DOCTYPE html><html></html>"));
+        assert!(sniffer.is_html("page", b"\n  <HTML lang=\"en\">"));
+        assert!(sniffer.is_html("page", b"<p>text</p>"));
+        assert!(!sniffer.is_html("page", b"plain text"));
+        assert!(!sniffer.is_html("page", b"<html"));
+        assert!(!sniffer.is_html("page", b"<paragraph>"));
+    }
+
+    #[test]
+    fn byte_order_marks_are_skipped() {
+        let sniffer = sniffer();
+        assert!(sniffer.is_html("page", b"\xEF\xBB\xBF<html>"));
+        assert!(sniffer.is_html("page", &utf16le("<html><body>")));
+        assert!(sniffer.is_html("page", &utf16be("<html><body>")));
+        assert!(!sniffer.is_html("page", &utf16le("plain text")));
+    }
+
+    #[test]
+    fn xml_needs_the_xhtml_namespace() {
+        let sniffer = sniffer();
+        let xhtml = b"<?xml version=\"1.0\"?>\n<html xmlns=\"http://www.w3.org/1999/xhtml\">";
+        assert!(sniffer.is_html("chapter", xhtml));
+        assert!(sniffer.is_html("chapter", b"<?xml version=\"1.0\"?><!DOCTYPE html><html>"));
+        assert!(!sniffer.is_html("feed", b"<?xml version=\"1.0\"?><rss version=\"2.0\">"));
+    }
+
+    #[test]
+    fn fragments_are_html() {
+        let sniffer = sniffer();
+        assert!(sniffer.is_html("snippet", b"<article><h2>Title</h2>"));
+        assert!(sniffer.is_html("snippet", b"<ul>\n<li>one</li>"));
+        assert!(sniffer.is_html("snippet", b"<img src=\"a.png\"/>"));
+        assert!(sniffer.is_html("snippet", b"Hello <meta charset=\"utf-8\">"));
+    }
+
+    #[test]
+    fn binary_data_is_not_html_whatever_its_name() {
+        let sniffer = sniffer();
+        assert!(sniffer.is_html("page.html", b"no markup at all"));
+        assert!(sniffer.is_html("PAGE.XHTML", b""));
+        assert!(!sniffer.is_html("page.html", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!sniffer.is_html("page.htm", b"<html>\0\0"));
+    }
+
+    #[test]
+    fn glob_overrides_come_first() {
+        let sniffer = Sniffer::new(
+            &["*.tmpl".to_string(), "keep/*.html".to_string()],
+            &["*.html".to_string(), "*.txt".to_string()],
+        )
+        .unwrap();
+        assert!(sniffer.is_html("page.tmpl", b"{{ title }}"));
+        assert!(!sniffer.is_html("page.html", b"<html>"));
+        assert!(sniffer.is_html("keep/page.html", b"<html>"));
+        assert!(!sniffer.is_html("notes.txt", b"<p>text</p>"));
+        assert!(Sniffer::new(&["[".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use super::{enclosed_path, modified, Entry, OnEntry, Source};
+use crate::error::Context;
+use crate::MyError;
+use std::path::{Path, PathBuf};
+
+/// An already unpacked directory tree, walked in sorted order.
+pub(crate) struct DirSource {
+    root: PathBuf,
+}
+
+impl DirSource {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// Name of the entry at `path`, relative to the root and `/` separated.
+    fn name(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl Source for DirSource {
+    fn kind(&self) -> String {
+        "directory".into()
+    }
+
+    fn for_each_entry(&mut self, f: &mut OnEntry) -> Result<(), MyError> {
+        let walk = walkdir::WalkDir::new(&self.root)
+            .min_depth(1)
+            .sort_by_file_name();
+        for dir_entry in walk {
+            let dir_entry = match dir_entry {
+                Ok(dir_entry) => dir_entry,
+                Err(e) => {
+                    let path = e.path().unwrap_or(&self.root).to_path_buf();
+                    let name = self.name(&path);
+                    let error = MyError::Input {
+                        path,
+                        source: e.into(),
+                    };
+                    // Nothing can be read if the root itself can not.
+                    if name.is_empty() {
+                        return Err(error);
+                    }
+                    f(Err(error.in_entry(&name, enclosed_path(&name).as_deref())))?;
+                    continue;
+                }
+            };
+            let file_type = dir_entry.file_type();
+            if !file_type.is_dir() && !file_type.is_file() {
+                continue;
+            }
+            let mut name = self.name(dir_entry.path());
+            if file_type.is_dir() {
+                name.push('/');
+                f(Ok(Entry {
+                    path: enclosed_path(&name),
+                    is_dir: true,
+                    size: None,
+                    compressed_size: None,
+                    method: None,
+                    url: None,
+                    date: None,
+                    mtime: None,
+                    name,
+                    content: &mut std::io::empty(),
+                }))?;
+            } else {
+                let opened = std::fs::File::open(dir_entry.path())
+                    .and_then(|file| Ok((file.metadata()?, file)))
+                    .reading(dir_entry.path());
+                let (metadata, mut file) = match opened {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        f(Err(e.in_entry(&name, enclosed_path(&name).as_deref())))?;
+                        continue;
+                    }
+                };
+                let mtime = modified(&metadata);
+                f(Ok(Entry {
+                    path: enclosed_path(&name),
+                    is_dir: false,
+                    size: Some(metadata.len()),
+                    compressed_size: None,
+                    method: None,
+                    url: None,
+                    date: None,
+                    mtime,
+                    name,
+                    content: &mut file,
+                }))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use super::{
+    enclosed_path, header, html_name, mime_type, unique_name, url_name, Entry, OnEntry, Source,
+};
+use crate::MyError;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use std::collections::HashSet;
+use std::io::Read;
+
+/// Base64 as found in the wild, with or without padding.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+const MHTML_EXTENSIONS: &[&str] = &["mht", "mhtml"];
+
+/// Does the input called `name`, starting with the bytes `head`, look like an
+/// MHTML web archive?
+pub(crate) fn is_mhtml(name: &str, head: &[u8]) -> bool {
+    let extension = name.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase());
+    if extension.is_some_and(|e| MHTML_EXTENSIONS.contains(&&e[..])) {
+        return true;
+    }
+    let (headers, _) = split_entity(head);
+    header(&headers, "content-type").is_some_and(|c| mime_type(c) == "multipart/related")
+}
+
+/// A browser-saved web page, one MIME multipart message holding the page and
+/// the resources it uses.
+///
+/// Parts are named after their `Content-Location`, so the page and its images
+/// and stylesheets keep their layout below the output directory.
+pub(crate) struct MhtmlSource {
+    parts: Vec<(String, Vec<u8>)>,
+}
+
+impl MhtmlSource {
+    pub fn new(mut reader: impl Read) -> Result<Self, MyError> {
+        let mut message = Vec::new();
+        reader.read_to_end(&mut message)?;
+        let mut source = Self { parts: Vec::new() };
+        source.collect(&message, &mut HashSet::new())?;
+        Ok(source)
+    }
+
+    /// Decode the leaf parts of `entity`, descending into nested multiparts.
+    fn collect(&mut self, entity: &[u8], names: &mut HashSet<String>) -> Result<(), MyError> {
+        let (headers, body) = split_entity(entity);
+        let content_type = header(&headers, "content-type").unwrap_or("text/plain");
+        let mime = mime_type(content_type);
+        if mime.starts_with("multipart/") {
+            if let Some(boundary) = param(content_type, "boundary") {
+                for part in split_multipart(body, &boundary) {
+                    self.collect(part, names)?;
+                }
+                return Ok(());
+            }
+        }
+        let mut content = match header(&headers, "content-transfer-encoding")
+            .map(|e| e.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("base64") => {
+                let encoded = body
+                    .iter()
+                    .copied()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .collect::<Vec<u8>>();
+                BASE64
+                    .decode(encoded)
+                    .map_err(|_| MyError::Corrupt("Malformed base64 part in MHTML input".into()))?
+            }
+            Some("quoted-printable") => {
+                quoted_printable::decode(body, quoted_printable::ParseMode::Robust).map_err(
+                    |_| MyError::Corrupt("Malformed quoted-printable part in MHTML input".into()),
+                )?
+            }
+            _ => body.to_vec(),
+        };
+        if mime == "text/html" {
+            content = to_utf8(content, param(content_type, "charset"));
+        }
+        let name = part_name(
+            header(&headers, "content-location"),
+            &mime,
+            self.parts.len(),
+        );
+        let name = unique_name(name, names);
+        self.parts.push((name, content));
+        Ok(())
+    }
+}
+
+impl Source for MhtmlSource {
+    fn kind(&self) -> String {
+        "mhtml".into()
+    }
+
+    fn for_each_entry(&mut self, f: &mut OnEntry) -> Result<(), MyError> {
+        for (name, content) in &self.parts {
+            f(Ok(Entry {
+                path: enclosed_path(name),
+                is_dir: false,
+                size: Some(content.len() as u64),
+                compressed_size: None,
+                method: None,
+                url: None,
+                date: None,
+                mtime: None,
+                name: name.clone(),
+                content: &mut &content[..],
+            }))?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a MIME entity into its headers, with lowercased names and unfolded
+/// values, and its body.
+fn split_entity(entity: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let (head, body) = match find_blank_line(entity) {
+        Some((end, start)) => (&entity[..end], &entity[start..]),
+        None => (entity, &entity[entity.len()..]),
+    };
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    (headers, body)
+}
+
+/// End of the headers and start of the body, around the first empty line.
+fn find_blank_line(entity: &[u8]) -> Option<(usize, usize)> {
+    if entity.starts_with(b"\r\n") {
+        return Some((0, 2));
+    }
+    if entity.starts_with(b"\n") {
+        return Some((0, 1));
+    }
+    let lf = find(entity, b"\n\n").map(|i| (i + 1, i + 2));
+    let crlf = find(entity, b"\n\r\n").map(|i| (i + 1, i + 3));
+    match (lf, crlf) {
+        (Some(lf), Some(crlf)) => Some(lf.min(crlf)),
+        (lf, crlf) => lf.or(crlf),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Value of the parameter `name` of a `Content-Type` header.
+fn param(content_type: &str, name: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|p| {
+        let (key, value) = p.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// The parts of a multipart body between its `--boundary` delimiter lines.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+    while offset < body.len() {
+        let end = body[offset..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(body.len(), |i| offset + i + 1);
+        let line = String::from_utf8_lossy(&body[offset..end]);
+        let line = line.trim_end();
+        if line == delimiter || line == format!("{}--", delimiter) {
+            if let Some(start) = start {
+                // The line break before a delimiter belongs to the delimiter.
+                let mut part = &body[start..offset];
+                part = part.strip_suffix(b"\n").unwrap_or(part);
+                part = part.strip_suffix(b"\r").unwrap_or(part);
+                parts.push(part);
+            }
+            if line != delimiter {
+                break;
+            }
+            start = Some(end);
+        }
+        offset = end;
+    }
+    parts
+}
+
+/// Transcode an HTML part to UTF-8 from the charset of its `Content-Type`.
+///
+/// The result starts with a byte order mark so it wins over a `<meta charset>`
+/// the page may still carry.
+fn to_utf8(content: Vec<u8>, charset: Option<String>) -> Vec<u8> {
+    let encoding = match charset.and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes())) {
+        Some(encoding) if encoding != encoding_rs::UTF_8 => encoding,
+        _ => return content,
+    };
+    let (text, _) = encoding.decode_with_bom_removal(&content);
+    let mut utf8 = b"\xEF\xBB\xBF".to_vec();
+    utf8.extend_from_slice(text.as_bytes());
+    utf8
+}
+
+/// Name of a part from its `Content-Location`: host and path for URLs,
+/// `cid/` followed by the id for `cid:` references, falling back to the index
+/// of the part.
+fn part_name(location: Option<&str>, mime: &str, index: usize) -> String {
+    let location = location.unwrap_or_default();
+    let mut name = match location.strip_prefix("cid:") {
+        Some(id) => format!("cid/{}", id.trim_matches(['<', '>'])),
+        None => url_name(location),
+    };
+    if name.is_empty() {
+        name = format!("part-{}", index);
+    }
+    if mime == "text/html" {
+        name = html_name(name);
+    }
+    name
+}
@@ -0,0 +1,414 @@
+//! Inputs the extractor can read entries from.
+//!
+//! Every kind of input is exposed as a [`Source`] which hands out its entries
+//! one by one, so the HTML rendering and copying of the rest of the files is
+//! shared between all of them.
+
+mod dir;
+mod mhtml;
+mod single;
+mod tar;
+mod warc;
+mod zip;
+
+use crate::epub::EpubMetadata;
+use crate::error::{is_corrupt, Context};
+use crate::sniff::{self, Sniffer};
+use crate::MyError;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+pub(crate) use self::dir::DirSource;
+pub(crate) use self::mhtml::MhtmlSource;
+pub(crate) use self::single::SingleSource;
+pub(crate) use self::tar::{Compression, TarSource};
+pub(crate) use self::warc::WarcSource;
+pub(crate) use self::zip::ZipSource;
+
+/// A single file or directory of a source.
+pub(crate) struct Entry<'a> {
+    /// Name of the entry as stored in the source, `/` separated.
+    pub name: String,
+    /// Sanitized relative path of the entry, `None` if the name would escape
+    /// the output directory.
+    pub path: Option<PathBuf>,
+    pub is_dir: bool,
+    /// Uncompressed size as declared by the source, if known up front.
+    pub size: Option<u64>,
+    /// Size of the entry as stored in the source, if it is compressed.
+    pub compressed_size: Option<u64>,
+    /// How the entry is compressed, e.g. `Deflated`, if the source says.
+    pub method: Option<String>,
+    /// Address the entry was captured from, for web archive records.
+    pub url: Option<String>,
+    /// When the entry was captured, for web archive records.
+    pub date: Option<String>,
+    /// Last modification time as an ISO 8601 timestamp, if the source has it.
+    pub mtime: Option<String>,
+    pub content: &'a mut dyn Read,
+}
+
+/// Called with every entry of a source, or the error of one which could not be
+/// read.
+pub(crate) type OnEntry<'a> = dyn FnMut(Result<Entry, MyError>) -> Result<(), MyError> + 'a;
+
+pub(crate) trait Source {
+    /// Name of the input format, e.g. `zip` or `tar.gz`.
+    fn kind(&self) -> String;
+
+    /// Publication metadata of the source, if it has any.
+    fn metadata(&self) -> Option<&EpubMetadata> {
+        None
+    }
+
+    /// Call `f` with every entry of the source, in the order they should be
+    /// output, or with the [`MyError::Entry`] of an entry which could not be
+    /// read, so the run can go on with the next one in keep-going mode.
+    fn for_each_entry(&mut self, f: &mut OnEntry) -> Result<(), MyError>;
+}
+
+/// Open the input at `path`, detecting its format from its leading bytes.
+pub(crate) fn open(
+    path: &Path,
+    epub: bool,
+    sniffer: &Sniffer,
+    max_size: u64,
+) -> Result<Box<dyn Source>, MyError> {
+    if !path.exists() {
+        return Err(MyError::Input {
+            path: path.to_path_buf(),
+            source: std::io::ErrorKind::NotFound.into(),
+        });
+    }
+    if path.is_dir() {
+        return Ok(Box::new(DirSource::new(path)));
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file = std::fs::File::open(path).reading(path)?;
+    let mtime = file.metadata().ok().as_ref().and_then(modified);
+    open_reader(file, &name, mtime, epub, sniffer, max_size).map_err(|e| match e {
+        MyError::Io(e) if is_corrupt(&e) => MyError::Corrupt(format!("Corrupt input: {}", e)),
+        MyError::Io(source) => MyError::Input {
+            path: path.to_path_buf(),
+            source,
+        },
+        e => e,
+    })
+}
+
+/// Archive formats recognised from their leading bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Format {
+    Zip,
+    Tar(Compression),
+    Warc,
+}
+
+/// Guess the archive format from the first bytes of a stream.
+///
+/// Compressed streams are reported as tarballs, whether they really contain
+/// one or rather a WARC file is only known after decompressing them.
+pub(crate) fn detect(magic: &[u8]) -> Option<Format> {
+    if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+        Some(Format::Zip)
+    } else if magic.starts_with(&[0x1F, 0x8B]) {
+        Some(Format::Tar(Compression::Gzip))
+    } else if magic.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+        Some(Format::Tar(Compression::Zstd))
+    } else if magic.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(Format::Tar(Compression::Xz))
+    } else if magic.starts_with(b"BZh") {
+        Some(Format::Tar(Compression::Bzip2))
+    } else if is_ustar(magic) {
+        Some(Format::Tar(Compression::None))
+    } else if magic.starts_with(b"WARC/") {
+        Some(Format::Warc)
+    } else {
+        None
+    }
+}
+
+fn is_ustar(header: &[u8]) -> bool {
+    header.len() >= 262 && &header[257..262] == b"ustar"
+}
+
+/// Open a seekable stream called `name`, detecting its format from its
+/// leading bytes.
+///
+/// Besides archives this accepts MHTML web archives and single HTML
+/// documents, as told by `sniffer`, which become a source with the document
+/// as its only entry, modified at `mtime`. Reading more than `max_size` bytes out of a tar or WARC
+/// stream fails, `0` for no limit.
+pub(crate) fn open_reader<R: Read + Seek + 'static>(
+    mut reader: R,
+    name: &str,
+    mtime: Option<String>,
+    epub: bool,
+    sniffer: &Sniffer,
+    max_size: u64,
+) -> Result<Box<dyn Source>, MyError> {
+    // Large enough for the headers of an MHTML message.
+    let mut magic = [0u8; 4096];
+    let len = read_up_to(&mut reader, &mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+    match detect(&magic[..len]) {
+        Some(Format::Zip) => Ok(Box::new(ZipSource::new(reader, epub)?)),
+        Some(Format::Tar(Compression::None)) => Ok(Box::new(TarSource::new(
+            reader,
+            Compression::None,
+            max_size,
+        )?)),
+        Some(Format::Tar(compression)) => {
+            let len = read_up_to(&mut tar::decoder(&mut reader, compression)?, &mut magic)?;
+            reader.seek(SeekFrom::Start(0))?;
+            match detect(&magic[..len]) {
+                Some(Format::Tar(Compression::None)) => {
+                    Ok(Box::new(TarSource::new(reader, compression, max_size)?))
+                }
+                Some(Format::Warc) => Ok(Box::new(WarcSource::new(reader, compression, max_size)?)),
+                _ => Err(MyError::Corrupt(
+                    "Compressed input does not contain a tar archive or WARC file".into(),
+                )),
+            }
+        }
+        Some(Format::Warc) => Ok(Box::new(WarcSource::new(
+            reader,
+            Compression::None,
+            max_size,
+        )?)),
+        None if mhtml::is_mhtml(name, &magic[..len]) => Ok(Box::new(MhtmlSource::new(reader)?)),
+        None if sniffer.is_html(name, &magic[..len]) => {
+            let size = reader.seek(SeekFrom::End(0))?;
+            reader.seek(SeekFrom::Start(0))?;
+            Ok(Box::new(SingleSource::new(name, Some(size), mtime, reader)))
+        }
+        None => Err(MyError::Corrupt("Unrecognized input format".into())),
+    }
+}
+
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, MyError> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+/// Turn an entry name into a relative path, refusing absolute paths and
+/// anything climbing out with `..`.
+pub(crate) fn enclosed_path(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    let mut enclosed = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => enclosed.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(enclosed)
+}
+
+/// ISO 8601 UTC timestamp of `secs` seconds since the Unix epoch.
+/// Last modification time of a file as an ISO 8601 timestamp.
+pub(crate) fn modified(metadata: &std::fs::Metadata) -> Option<String> {
+    let time = metadata.modified().ok()?;
+    let since = time.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(utc_timestamp(since.as_secs()))
+}
+
+pub(crate) fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+    // Civil date from days since 1970-01-01, after Howard Hinnant.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Value of the header field `name` among lowercased header names.
+pub(crate) fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// `text/html` out of `Text/HTML; charset=utf-8`.
+pub(crate) fn mime_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Relative name for a captured URL, its host followed by its path.
+/// Directories are named after their index page.
+pub(crate) fn url_name(url: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let (url, has_host) = match url.split_once("://") {
+        Some((_, rest)) => (rest, true),
+        None => (url, false),
+    };
+    let mut name = url
+        .split('/')
+        .filter(|s| !s.is_empty() && *s != "." && *s != "..")
+        .collect::<Vec<_>>()
+        .join("/");
+    if !name.is_empty() && (url.ends_with('/') || has_host && !url.contains('/')) {
+        name.push_str("/index.html");
+    }
+    name
+}
+
+/// Give the name of an HTML document an HTML extension if it lacks one.
+pub(crate) fn html_name(mut name: String) -> String {
+    if !sniff::has_html_extension(&name) {
+        name.push_str(".html");
+    }
+    name
+}
+
+/// Make `name` unique among `names` by numbering repeated names.
+pub(crate) fn unique_name(name: String, names: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut n = 1;
+    while names.contains(&unique) {
+        unique = match name.rsplit_once('.') {
+            Some((stem, extension)) if !extension.contains('/') => {
+                format!("{}-{}.{}", stem, n, extension)
+            }
+            _ => format!("{}-{}", name, n),
+        };
+        n += 1;
+    }
+    names.insert(unique.clone());
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_recognises_every_magic() {
+        assert_eq!(detect(b"PK\x03\x04rest"), Some(Format::Zip));
+        assert_eq!(detect(b"PK\x05\x06"), Some(Format::Zip));
+        assert_eq!(
+            detect(&[0x1F, 0x8B, 0x08]),
+            Some(Format::Tar(Compression::Gzip))
+        );
+        assert_eq!(
+            detect(&[0x28, 0xB5, 0x2F, 0xFD]),
+            Some(Format::Tar(Compression::Zstd))
+        );
+        assert_eq!(detect(b"\xFD7zXZ\0"), Some(Format::Tar(Compression::Xz)));
+        assert_eq!(detect(b"BZh91AY"), Some(Format::Tar(Compression::Bzip2)));
+        let mut tar = vec![0u8; 512];
+        tar[257..263].copy_from_slice(b"ustar\0");
+        assert_eq!(detect(&tar), Some(Format::Tar(Compression::None)));
+        assert_eq!(detect(b"WARC/1.1\r\n"), Some(Format::Warc));
+        assert_eq!(detect(b"<html>"), None);
+        assert_eq!(detect(b"PK"), None);
+        assert_eq!(detect(&tar[..261]), None);
+        assert_eq!(detect(b""), None);
+    }
+
+    #[test]
+    fn enclosed_path_refuses_escaping_names() {
+        assert_eq!(enclosed_path("a/b.html"), Some(PathBuf::from("a/b.html")));
+        assert_eq!(
+            enclosed_path("./a/./b.html"),
+            Some(PathBuf::from("a/b.html"))
+        );
+        assert_eq!(enclosed_path("dir/"), Some(PathBuf::from("dir")));
+        assert_eq!(enclosed_path("../b.html"), None);
+        assert_eq!(enclosed_path("a/../../b.html"), None);
+        assert_eq!(enclosed_path("a/../b.html"), None);
+        assert_eq!(enclosed_path("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn utc_timestamp_of_known_epochs() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(946_684_799), "1999-12-31T23:59:59Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(1_709_251_199), "2024-02-29T23:59:59Z");
+        // 2100 is not a leap year.
+        assert_eq!(utc_timestamp(4_107_499_200), "2100-02-28T12:00:00Z");
+        assert_eq!(utc_timestamp(4_107_542_400), "2100-03-01T00:00:00Z");
+    }
+
+    #[test]
+    fn url_name_is_host_and_path() {
+        assert_eq!(url_name("http://example.com"), "example.com/index.html");
+        assert_eq!(url_name("http://example.com/"), "example.com/index.html");
+        assert_eq!(
+            url_name("https://example.com/a/b/"),
+            "example.com/a/b/index.html"
+        );
+        assert_eq!(
+            url_name("https://example.com/a/page.html?q=1#top"),
+            "example.com/a/page.html"
+        );
+        assert_eq!(url_name("https://example.com/a//./../b"), "example.com/a/b");
+        assert_eq!(url_name("/relative/page"), "relative/page");
+        assert_eq!(url_name("?only=query"), "");
+    }
+
+    #[test]
+    fn html_name_adds_a_missing_extension() {
+        assert_eq!(
+            html_name("example.com/page".into()),
+            "example.com/page.html"
+        );
+        assert_eq!(
+            html_name("example.com/page.htm".into()),
+            "example.com/page.htm"
+        );
+    }
+
+    #[test]
+    fn unique_name_numbers_repeated_names() {
+        let mut names = HashSet::new();
+        assert_eq!(unique_name("a/page.html".into(), &mut names), "a/page.html");
+        assert_eq!(
+            unique_name("a/page.html".into(), &mut names),
+            "a/page-1.html"
+        );
+        assert_eq!(
+            unique_name("a/page.html".into(), &mut names),
+            "a/page-2.html"
+        );
+        assert_eq!(unique_name("a.d/page".into(), &mut names), "a.d/page");
+        assert_eq!(unique_name("a.d/page".into(), &mut names), "a.d/page-1");
+        // A numbered name taken by an entry of its own is skipped.
+        assert_eq!(unique_name("b-1.txt".into(), &mut names), "b-1.txt");
+        assert_eq!(unique_name("b.txt".into(), &mut names), "b.txt");
+        assert_eq!(unique_name("b.txt".into(), &mut names), "b-2.txt");
+    }
+}
@@ -0,0 +1,48 @@
+use super::{enclosed_path, Entry, OnEntry, Source};
+use crate::MyError;
+use std::io::Read;
+
+/// A lone document, handed out as the only entry.
+pub(crate) struct SingleSource {
+    name: String,
+    size: Option<u64>,
+    mtime: Option<String>,
+    reader: Box<dyn Read>,
+}
+
+impl SingleSource {
+    pub fn new(
+        name: &str,
+        size: Option<u64>,
+        mtime: Option<String>,
+        reader: impl Read + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            size,
+            mtime,
+            reader: Box::new(reader),
+        }
+    }
+}
+
+impl Source for SingleSource {
+    fn kind(&self) -> String {
+        "html".into()
+    }
+
+    fn for_each_entry(&mut self, f: &mut OnEntry) -> Result<(), MyError> {
+        f(Ok(Entry {
+            path: enclosed_path(&self.name),
+            is_dir: false,
+            size: self.size,
+            compressed_size: None,
+            method: None,
+            url: None,
+            date: None,
+            mtime: self.mtime.clone(),
+            name: self.name.clone(),
+            content: &mut self.reader,
+        }))
+    }
+}
@@ -0,0 +1,141 @@
+use super::{enclosed_path, Entry, OnEntry, Source};
+use crate::limits::StreamLimit;
+use crate::MyError;
+use std::io::Read;
+use tar::{Archive, EntryType};
+
+/// Compression applied on top of a tarball.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    /// File extension of the compression, `None` if there is none.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+            Compression::Xz => Some("xz"),
+            Compression::Bzip2 => Some("bz2"),
+        }
+    }
+
+    /// Name of the format `name` compressed with this, e.g. `tar.gz`.
+    pub fn kind(self, name: &str) -> String {
+        match self.extension() {
+            Some(extension) => format!("{}.{}", name, extension),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// Tar archives, optionally compressed.
+pub(crate) struct TarSource {
+    archive: Archive<StreamLimit<Box<dyn Read>>>,
+    compression: Compression,
+}
+
+impl TarSource {
+    /// Reading more than `max_size` bytes of the decompressed stream, entry
+    /// headers such as long names included, fails.
+    pub fn new(
+        reader: impl Read + 'static,
+        compression: Compression,
+        max_size: u64,
+    ) -> Result<Self, MyError> {
+        Ok(Self {
+            archive: Archive::new(StreamLimit::new(decoder(reader, compression)?, max_size)),
+            compression,
+        })
+    }
+}
+
+/// Wrap `reader` in the decompressor for `compression`.
+pub(crate) fn decoder<'a>(
+    reader: impl Read + 'a,
+    compression: Compression,
+) -> Result<Box<dyn Read + 'a>, MyError> {
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new(reader)),
+        Compression::Bzip2 => Box::new(bzip2::read::BzDecoder::new(reader)),
+    })
+}
+
+impl Source for TarSource {
+    fn kind(&self) -> String {
+        self.compression.kind("tar")
+    }
+
+    fn for_each_entry(&mut self, f: &mut OnEntry) -> Result<(), MyError> {
+        for entry in self.archive.entries().map_err(corrupt)? {
+            let mut entry = entry.map_err(corrupt)?;
+            let is_dir = match entry.header().entry_type() {
+                EntryType::Directory => true,
+                EntryType::Regular | EntryType::Continuous => false,
+                _ => continue,
+            };
+            let name = entry_name(&entry.path_bytes());
+            // The `./` most tarballs start with.
+            if name.is_empty() {
+                continue;
+            }
+            f(Ok(Entry {
+                path: enclosed_path(&name),
+                is_dir,
+                size: entry.header().size().ok(),
+                compressed_size: None,
+                // The whole stream is compressed, not the single entries.
+                method: None,
+                url: None,
+                date: None,
+                mtime: entry.header().mtime().ok().map(super::utc_timestamp),
+                name,
+                content: &mut entry,
+            }))?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors reading the tar headers mean a corrupt archive, unless the stream
+/// went over a limit.
+fn corrupt(error: std::io::Error) -> MyError {
+    match MyError::from(error) {
+        MyError::Io(e) => MyError::Corrupt(format!("Corrupt tar archive: {}", e)),
+        e => e,
+    }
+}
+
+/// Name of a tar entry without the `.` components of its path, so
+/// `./docs/index.html` is called `docs/index.html` as in other archives.
+fn entry_name(path: &[u8]) -> String {
+    String::from_utf8_lossy(path)
+        .split('/')
+        .filter(|c| *c != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_names_lose_dot_components() {
+        assert_eq!(entry_name(b"./index.html"), "index.html");
+        assert_eq!(entry_name(b"./docs/./a.html"), "docs/a.html");
+        assert_eq!(entry_name(b"./docs/"), "docs/");
+        assert_eq!(entry_name(b"./"), "");
+        assert_eq!(entry_name(b"/etc/passwd"), "/etc/passwd");
+        assert_eq!(entry_name(b"../up.html"), "../up.html");
+    }
+}
@@ -0,0 +1,468 @@
+use super::tar::{decoder, Compression};
+use super::{
+    enclosed_path, header, html_name, mime_type, unique_name, url_name, Entry, OnEntry, Source,
+};
+use crate::limits::StreamLimit;
+use crate::MyError;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read, Take};
+
+/// Start line and header fields of a WARC record or HTTP message.
+type Head = (String, Vec<(String, String)>);
+
+/// Content types of the payloads rendered as HTML.
+const HTML_TYPES: &[&str] = &["text/html", "application/xhtml+xml"];
+
+/// WARC web archives as written by crawlers, optionally compressed.
+///
+/// Only successful HTTP `response` records become entries, named after the
+/// host and path of their target URI. Their payload is handed out with the
+/// transfer and content encodings of the capture undone.
+pub(crate) struct WarcSource {
+    reader: BufReader<StreamLimit<Box<dyn Read>>>,
+    names: HashSet<String>,
+    compression: Compression,
+}
+
+impl WarcSource {
+    /// Reading more than `max_size` bytes of the decompressed stream, records
+    /// which do not become entries included, fails.
+    pub fn new(
+        reader: impl Read + 'static,
+        compression: Compression,
+        max_size: u64,
+    ) -> Result<Self, MyError> {
+        Ok(Self {
+            reader: BufReader::new(StreamLimit::new(decoder(reader, compression)?, max_size)),
+            names: HashSet::new(),
+            compression,
+        })
+    }
+}
+
+impl Source for WarcSource {
+    fn kind(&self) -> String {
+        self.compression.kind("warc")
+    }
+
+    fn for_each_entry(&mut self, f: &mut OnEntry) -> Result<(), MyError> {
+        while let Some((version, headers)) = read_head(&mut self.reader)? {
+            if !version.starts_with("WARC/") {
+                return Err(MyError::Corrupt("Malformed WARC record".into()));
+            }
+            let length = header(&headers, "content-length")
+                .and_then(|l| l.parse::<u64>().ok())
+                .ok_or_else(|| {
+                    MyError::Corrupt("WARC record without a valid Content-Length".into())
+                })?;
+            let mut block = (&mut self.reader).take(length);
+            let is_response = header(&headers, "warc-type") == Some("response")
+                && header(&headers, "content-type")
+                    .is_some_and(|t| mime_type(t) == "application/http");
+            let url = header(&headers, "warc-target-uri").map(|u| u.trim_matches(['<', '>']));
+            if let (true, Some(url)) = (is_response, url) {
+                let date = header(&headers, "warc-date").map(str::to_string);
+                response(&mut block, url.to_string(), date, &mut self.names, f)?;
+            }
+            // Skip whatever of the block the entry did not read.
+            std::io::copy(&mut block, &mut std::io::sink())?;
+        }
+        Ok(())
+    }
+}
+
+/// Hand out the payload of the HTTP response in a `response` record.
+fn response<R: BufRead>(
+    block: &mut Take<R>,
+    url: String,
+    date: Option<String>,
+    names: &mut HashSet<String>,
+    f: &mut OnEntry,
+) -> Result<(), MyError> {
+    let (status, headers) = match read_head(&mut *block)? {
+        Some(head) => head,
+        None => return Ok(()),
+    };
+    // Redirects and errors do not hold the captured page.
+    if !status
+        .split_whitespace()
+        .nth(1)
+        .is_some_and(|code| code.starts_with('2'))
+    {
+        return Ok(());
+    }
+    let mut name = url_name(&url);
+    if name.is_empty() {
+        return Ok(());
+    }
+    let content_type = header(&headers, "content-type")
+        .map(mime_type)
+        .unwrap_or_default();
+    if HTML_TYPES.contains(&&content_type[..]) {
+        name = html_name(name);
+    }
+    let name = unique_name(name, names);
+
+    let chunked =
+        header(&headers, "transfer-encoding").is_some_and(|t| t.eq_ignore_ascii_case("chunked"));
+    // What is left of the block, for a chunked payload including the chunk
+    // sizes, and so an upper bound of the payload size.
+    let length = block.limit();
+    let payload: Box<dyn Read + '_> = if chunked {
+        Box::new(Dechunked::new(block))
+    } else {
+        Box::new(block)
+    };
+    let encoding = header(&headers, "content-encoding").map(|e| e.to_ascii_lowercase());
+    let (mut content, size, compressed_size): (Box<dyn Read + '_>, _, _) = match encoding.as_deref()
+    {
+        Some("gzip") | Some("x-gzip") => (
+            Box::new(flate2::read::MultiGzDecoder::new(payload)),
+            None,
+            Some(length),
+        ),
+        Some("deflate") => (
+            Box::new(flate2::read::ZlibDecoder::new(payload)),
+            None,
+            Some(length),
+        ),
+        _ if chunked => (payload, None, None),
+        _ => (payload, Some(length), None),
+    };
+    f(Ok(Entry {
+        path: enclosed_path(&name),
+        is_dir: false,
+        size,
+        compressed_size,
+        // The content encoding, if it was undone.
+        method: compressed_size.and(encoding),
+        name,
+        url: Some(url),
+        mtime: date.clone(),
+        date,
+        content: &mut content,
+    }))
+}
+
+/// Longest start or header line accepted.
+const MAX_HEAD_LINE: u64 = 64 << 10;
+
+/// Most header lines accepted in a WARC record or HTTP message, continuation
+/// lines included.
+const MAX_HEAD_LINES: usize = 256;
+
+/// Read the start line and the header fields of a WARC record or HTTP
+/// message, skipping the blank lines in front of it. `None` at the end of the
+/// input.
+fn read_head(reader: &mut impl BufRead) -> Result<Option<Head>, MyError> {
+    let mut start = String::new();
+    while start.is_empty() {
+        match read_line(reader)? {
+            Some(line) => start = line,
+            None => return Ok(None),
+        }
+    }
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut lines = 0;
+    while let Some(line) = read_line(reader)? {
+        if line.is_empty() {
+            break;
+        }
+        lines += 1;
+        if lines > MAX_HEAD_LINES {
+            return Err(MyError::Corrupt(format!(
+                "More than {} header lines in WARC record",
+                MAX_HEAD_LINES
+            )));
+        }
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    Ok(Some((start, headers)))
+}
+
+/// A line without its line break, `None` at the end of the input.
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>, MyError> {
+    let mut line = Vec::new();
+    if reader
+        .take(MAX_HEAD_LINE + 1)
+        .read_until(b'\n', &mut line)?
+        == 0
+    {
+        return Ok(None);
+    }
+    if line.len() as u64 > MAX_HEAD_LINE && !line.ends_with(b"\n") {
+        return Err(MyError::Corrupt(format!(
+            "Line of more than {} bytes in WARC record",
+            MAX_HEAD_LINE
+        )));
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+    ))
+}
+
+/// Longest chunk size line accepted, extensions included.
+const MAX_CHUNK_LINE: u64 = 4096;
+
+/// Undoes the chunked transfer encoding of an HTTP payload while it is read,
+/// so the entry limits apply to it like to any other content.
+struct Dechunked<R> {
+    inner: R,
+    /// Bytes left of the current chunk.
+    left: u64,
+    /// Whether a chunk was read, which is followed by a line break.
+    started: bool,
+    done: bool,
+}
+
+impl<R: BufRead> Dechunked<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            left: 0,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// The next line, without its line break, empty at the end of the input.
+    fn line(&mut self) -> std::io::Result<String> {
+        let mut line = Vec::new();
+        (&mut self.inner)
+            .take(MAX_CHUNK_LINE)
+            .read_until(b'\n', &mut line)?;
+        Ok(String::from_utf8_lossy(&line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string())
+    }
+}
+
+impl<R: BufRead> Read for Dechunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.left == 0 && !self.done {
+            if self.started {
+                self.line()?;
+            }
+            self.started = true;
+            let line = self.line()?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            self.left = u64::from_str_radix(size, 16).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Malformed chunked payload in WARC record",
+                )
+            })?;
+            self.done = self.left == 0;
+        }
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = buf
+            .len()
+            .min(usize::try_from(self.left).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A WARC record of `warc_type` whose block is `block`.
+    fn record(warc_type: &str, url: &str, content_type: &str, block: &[u8]) -> Vec<u8> {
+        let mut record = format!(
+            "WARC/1.1\r\nWARC-Type: {}\r\nWARC-Target-URI: <{}>\r\n\
+             WARC-Date: 2024-02-29T12:00:00Z\r\nContent-Type: {}\r\n\
+             Content-Length: {}\r\n\r\n",
+            warc_type,
+            url,
+            content_type,
+            block.len()
+        )
+        .into_bytes();
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+        record
+    }
+
+    /// A `response` record holding an HTTP response.
+    fn response(url: &str, status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut block = format!("HTTP/1.1 {}\r\n{}\r\n", status, headers).into_bytes();
+        block.extend_from_slice(body);
+        record(
+            "response",
+            url,
+            "application/http; msgtype=response",
+            &block,
+        )
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Name, size, method and content of every entry of `warc`.
+    fn entries(warc: Vec<u8>) -> Vec<(String, Option<u64>, Option<String>, String)> {
+        let mut source = WarcSource::new(std::io::Cursor::new(warc), Compression::None, 0).unwrap();
+        let mut entries = Vec::new();
+        source
+            .for_each_entry(&mut |entry| {
+                let entry = entry?;
+                let mut content = Vec::new();
+                entry.content.read_to_end(&mut content)?;
+                let content = String::from_utf8_lossy(&content).into_owned();
+                entries.push((entry.name, entry.size, entry.method, content));
+                Ok(())
+            })
+            .unwrap();
+        entries
+    }
+
+    #[test]
+    fn only_successful_responses_become_entries() {
+        let html = "Content-Type: text/html\r\n";
+        let mut warc = record(
+            "warcinfo",
+            "",
+            "application/warc-fields",
+            b"software: test\r\n",
+        );
+        warc.extend(record(
+            "request",
+            "http://example.com/",
+            "application/http; msgtype=request",
+            b"GET / HTTP/1.1\r\n\r\n",
+        ));
+        warc.extend(response(
+            "http://example.com/",
+            "200 OK",
+            html,
+            b"<p>home</p>",
+        ));
+        warc.extend(response(
+            "http://example.com/old",
+            "301 Moved Permanently",
+            "Location: /new\r\n",
+            b"",
+        ));
+        warc.extend(response(
+            "http://example.com/gone",
+            "404 Not Found",
+            html,
+            b"<p>no</p>",
+        ));
+        warc.extend(response(
+            "http://example.com/logo.png",
+            "200 OK",
+            "Content-Type: image/png\r\n",
+            b"\x89PNG",
+        ));
+        let entries = entries(warc);
+        let names = entries.iter().map(|e| &e.0[..]).collect::<Vec<_>>();
+        assert_eq!(names, ["example.com/index.html", "example.com/logo.png"]);
+        assert_eq!(entries[0].1, Some(11));
+        assert_eq!(entries[0].3, "<p>home</p>");
+    }
+
+    #[test]
+    fn transfer_and_content_encodings_are_undone() {
+        let chunked = "Content-Type: text/html\r\nTransfer-Encoding: chunked\r\n";
+        let gzipped = "Content-Type: text/html; charset=utf-8\r\nContent-Encoding: gzip\r\n";
+        let body = gzip(b"<p>zipped</p>");
+        let mut both = format!("{:x}\r\n", body.len()).into_bytes();
+        both.extend_from_slice(&body);
+        both.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        let mut warc = response(
+            "https://example.com/page?id=1",
+            "200 OK",
+            chunked,
+            b"6\r\n<p>Hel\r\n7;ext=1\r\nlo</p>\n\r\n0\r\n\r\n",
+        );
+        warc.extend(response(
+            "https://example.com/page",
+            "200 OK",
+            gzipped,
+            &body,
+        ));
+        warc.extend(response(
+            "https://example.com/page",
+            "200 OK",
+            &format!("{}Content-Encoding: gzip\r\n", chunked),
+            &both,
+        ));
+        let entries = entries(warc);
+        assert_eq!(
+            entries,
+            [
+                (
+                    "example.com/page.html".into(),
+                    None,
+                    None,
+                    "<p>Hello</p>\n".into()
+                ),
+                (
+                    "example.com/page-1.html".into(),
+                    None,
+                    Some("gzip".into()),
+                    "<p>zipped</p>".into()
+                ),
+                (
+                    "example.com/page-2.html".into(),
+                    None,
+                    Some("gzip".into()),
+                    "<p>zipped</p>".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlong_heads_fail() {
+        let mut warc = b"WARC/1.1\r\nWARC-Type: ".to_vec();
+        warc.extend(vec![b'x'; MAX_HEAD_LINE as usize + 1]);
+        let mut source = WarcSource::new(std::io::Cursor::new(warc), Compression::None, 0).unwrap();
+        let res = source.for_each_entry(&mut |_| Ok(()));
+        assert!(matches!(res, Err(MyError::Corrupt(_))), "{:?}", res.err());
+
+        let mut warc = b"WARC/1.1\r\n".to_vec();
+        warc.extend(b"X-Field: value\r\n".repeat(MAX_HEAD_LINES + 1));
+        let mut source = WarcSource::new(std::io::Cursor::new(warc), Compression::None, 0).unwrap();
+        let res = source.for_each_entry(&mut |_| Ok(()));
+        assert!(matches!(res, Err(MyError::Corrupt(_))), "{:?}", res.err());
+    }
+
+    #[test]
+    fn malformed_chunks_fail() {
+        let warc = response(
+            "http://example.com/",
+            "200 OK",
+            "Transfer-Encoding: chunked\r\n",
+            b"zz\r\nnot a chunk\r\n0\r\n\r\n",
+        );
+        let mut source = WarcSource::new(std::io::Cursor::new(warc), Compression::None, 0).unwrap();
+        let res = source.for_each_entry(&mut |entry| {
+            let mut content = Vec::new();
+            entry?.content.read_to_end(&mut content)?;
+            Ok(())
+        });
+        assert!(res.is_err());
+    }
+}
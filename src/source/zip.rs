@@ -0,0 +1,114 @@
+use super::{enclosed_path, Entry, OnEntry, Source};
+use crate::epub::{self, Epub, EpubMetadata};
+use crate::MyError;
+use std::io::{Read, Seek};
+use zip::read::ZipArchive;
+use zip::result::ZipError;
+
+/// ZIP archives, including EPUB publications.
+pub(crate) struct ZipSource<R: Read + Seek> {
+    archive: ZipArchive<R>,
+    epub: Option<Epub>,
+}
+
+impl<R: Read + Seek> ZipSource<R> {
+    /// Open a ZIP archive, reading its EPUB package when `epub` is set or the
+    /// archive declares itself as one.
+    pub fn new(reader: R, epub: bool) -> Result<Self, MyError> {
+        let mut archive = ZipArchive::new(reader)?;
+        let epub = if epub || epub::is_epub(&mut archive) {
+            Some(epub::read(&mut archive)?)
+        } else {
+            None
+        };
+        Ok(Self { archive, epub })
+    }
+}
+
+impl<R: Read + Seek> Source for ZipSource<R> {
+    fn kind(&self) -> String {
+        match self.epub {
+            Some(_) => "epub".into(),
+            None => "zip".into(),
+        }
+    }
+
+    fn metadata(&self) -> Option<&EpubMetadata> {
+        self.epub.as_ref().map(|e| &e.metadata)
+    }
+
+    /// EPUB spine items come first, in reading order, and the packaging files
+    /// are left out.
+    fn for_each_entry(&mut self, f: &mut OnEntry) -> Result<(), MyError> {
+        if let Some(epub) = &self.epub {
+            for name in &epub.spine {
+                let mut chapter = match self.archive.by_name(name) {
+                    Ok(chapter) => chapter,
+                    Err(ZipError::FileNotFound) => continue,
+                    Err(e) => {
+                        f(Err(
+                            MyError::from(e).in_entry(name, enclosed_path(name).as_deref())
+                        ))?;
+                        continue;
+                    }
+                };
+                f(Ok(Entry {
+                    name: name.clone(),
+                    path: chapter.enclosed_name().map(|p| p.to_path_buf()),
+                    is_dir: false,
+                    size: Some(chapter.size()),
+                    compressed_size: Some(chapter.compressed_size()),
+                    method: Some(chapter.compression().to_string()),
+                    url: None,
+                    date: None,
+                    mtime: Some(timestamp(chapter.last_modified())),
+                    content: &mut chapter,
+                }))?;
+            }
+        }
+        for i in 0..self.archive.len() {
+            let mut archive_file = match self.archive.by_index(i) {
+                Ok(archive_file) => archive_file,
+                Err(e) => {
+                    // The name is only known once the entry could be read.
+                    let name = format!("#{}", i + 1);
+                    f(Err(MyError::from(e).in_entry(&name, None)))?;
+                    continue;
+                }
+            };
+            let name = archive_file.name().to_string();
+            if let Some(epub) = &self.epub {
+                if epub.spine.iter().chain(&epub.package).any(|n| *n == name) {
+                    continue;
+                }
+            }
+            f(Ok(Entry {
+                path: archive_file.enclosed_name().map(|p| p.to_path_buf()),
+                is_dir: archive_file.is_dir(),
+                size: Some(archive_file.size()),
+                compressed_size: Some(archive_file.compressed_size()),
+                method: Some(archive_file.compression().to_string()),
+                url: None,
+                date: None,
+                mtime: Some(timestamp(archive_file.last_modified())),
+                name,
+                content: &mut archive_file,
+            }))?;
+        }
+        Ok(())
+    }
+}
+
+/// ISO 8601 timestamp of a zip entry. Zip archives store local time without a
+/// timezone, so there is no `Z` suffix.
+fn timestamp(time: zip::DateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        time.year(),
+        time.month(),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
@@ -0,0 +1,307 @@
+//! Headers, footers and separators written around the documents of the text
+//! output.
+//!
+//! Templates may contain `{placeholder}`s which are replaced by the value of
+//! the document, and the escapes `\n`, `\t`, `\f` and `\\`. A placeholder
+//! followed by `:json`, as in `{title:json}`, is written as a JSON string, and
+//! `{{` and `}}` stand for literal braces.
+
+use crate::options::PRESETS;
+use crate::{Document, MyError, Options};
+
+const YAML_FRONT_MATTER: &str = "---\\ntitle: {title:json}\\npath: {path:json}\\n\
+    index: {index}\\nsize: {size}\\ncharset: {charset}\\nmtime: {mtime:json}\\n---";
+
+/// What is written around and between the documents.
+#[derive(Clone, Debug)]
+pub(crate) struct Frame {
+    header: Option<String>,
+    footer: Option<String>,
+    separator: String,
+}
+
+impl Frame {
+    /// The preset of `opts`, `markers` with artifacts and `blank` otherwise,
+    /// with the header, footer and separator given overriding its own.
+    pub fn new(opts: &Options) -> Result<Self, MyError> {
+        let default = if opts.file_artifacts {
+            "markers"
+        } else {
+            "blank"
+        };
+        // Without blank lines in the text, documents are not separated by one
+        // either.
+        let blank = if opts.whitespace == "strip" {
+            ""
+        } else {
+            "\\n"
+        };
+        let (header, footer, separator) = match opts.preset.as_deref().unwrap_or(default) {
+            "blank" => (None, None, blank),
+            "markers" => (Some("# begin {path}"), Some("# end {path}"), blank),
+            "form-feed" => (None, None, "\\f\\n"),
+            "yaml" => (Some(YAML_FRONT_MATTER), None, blank),
+            "none" => (None, None, ""),
+            preset => {
+                return Err(MyError::Usage(format!(
+                    "Unknown preset {}, expected one of {}",
+                    preset,
+                    PRESETS.join(", ")
+                )))
+            }
+        };
+        Ok(Self {
+            header: opts.header.as_deref().or(header).map(String::from),
+            footer: opts.footer.as_deref().or(footer).map(String::from),
+            separator: unescape(opts.separator.as_deref().unwrap_or(separator)),
+        })
+    }
+
+    /// Is there a header template, replacing the `# url:` and `# date:`
+    /// lines?
+    pub fn has_header(&self) -> bool {
+        self.header.is_some()
+    }
+
+    /// Header of the `index`th document, starting at 1.
+    pub fn header(&self, document: &Document, index: usize) -> Option<String> {
+        self.header
+            .as_ref()
+            .map(|t| line(expand(t, document, index)))
+    }
+
+    /// Footer of the `index`th document, starting at 1.
+    pub fn footer(&self, document: &Document, index: usize) -> Option<String> {
+        self.footer
+            .as_ref()
+            .map(|t| line(expand(t, document, index)))
+    }
+
+    /// Written between two documents.
+    pub fn separator(&self) -> &str {
+        &self.separator
+    }
+}
+
+/// End `text` with a line break, so the document starts on a line of its own.
+fn line(mut text: String) -> String {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// Replace the placeholders and escapes of `template`. Unknown placeholders
+/// are kept as they are.
+fn expand(template: &str, document: &Document, index: usize) -> String {
+    let template = unescape(template);
+    let mut out = String::new();
+    let mut rest = &template[..];
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let end = match tail.find('}') {
+            Some(end) if tail.starts_with('{') => end,
+            _ => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+                continue;
+            }
+        };
+        let placeholder = &tail[1..end];
+        let (name, json) = match placeholder.strip_suffix(":json") {
+            Some(name) => (name, true),
+            None => (placeholder, false),
+        };
+        match value(name, document, index) {
+            Some(value) if json => out.push_str(&serde_json::Value::from(value).to_string()),
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&tail[..=end]),
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Value of the placeholder `name`, empty if the document lacks it.
+fn value(name: &str, document: &Document, index: usize) -> Option<String> {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    Some(match name {
+        "path" => document.name.clone(),
+        "title" => optional(&document.title),
+        "index" => index.to_string(),
+        "size" => document.size.to_string(),
+        "charset" => document.charset.clone(),
+        "mtime" => optional(&document.mtime),
+        "url" => optional(&document.url),
+        "date" => optional(&document.date),
+        "crc32" => format!("{:08x}", document.crc32),
+        "words" => document.word_count().to_string(),
+        _ => return None,
+    })
+}
+
+/// Resolve the backslash escapes of a template given on the command line.
+fn unescape(template: &str) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('f') => out.push('\x0c'),
+            Some('\\') => out.push('\\'),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn document() -> Document {
+        Document {
+            name: "OEBPS/ch1.xhtml".into(),
+            path: PathBuf::from("OEBPS/ch1.xhtml"),
+            text: "Call me Ishmael.\n".into(),
+            title: Some("Loomings \"1\"".into()),
+            charset: "UTF-8".into(),
+            size: 42,
+            crc32: 0xbeef,
+            url: None,
+            date: None,
+            mtime: Some("2024-02-29T12:00:00".into()),
+        }
+    }
+
+    /// Header, footer and separator of the preset `preset`.
+    fn preset(preset: &str) -> (Option<String>, Option<String>, String) {
+        let frame = Frame::new(&Options::new().set_preset(preset)).unwrap();
+        let document = document();
+        (
+            frame.header(&document, 1),
+            frame.footer(&document, 1),
+            frame.separator().to_string(),
+        )
+    }
+
+    #[test]
+    fn placeholders_are_replaced() {
+        let document = document();
+        assert_eq!(
+            expand(
+                "{index}: {path} ({size} bytes, {crc32}, {words} words)",
+                &document,
+                3
+            ),
+            "3: OEBPS/ch1.xhtml (42 bytes, 0000beef, 3 words)"
+        );
+        assert_eq!(expand("[{url}]", &document, 1), "[]");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let document = document();
+        assert_eq!(
+            expand("{{path}} is {path}", &document, 1),
+            "{path} is OEBPS/ch1.xhtml"
+        );
+        assert_eq!(expand("}}{{", &document, 1), "}{");
+        assert_eq!(expand("a } b { c", &document, 1), "a } b { c");
+    }
+
+    #[test]
+    fn json_placeholders_are_quoted() {
+        let document = document();
+        assert_eq!(
+            expand("{title:json}", &document, 1),
+            "\"Loomings \\\"1\\\"\""
+        );
+        assert_eq!(expand("{index:json}", &document, 7), "\"7\"");
+        assert_eq!(expand("{date:json}", &document, 1), "\"\"");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        let document = document();
+        assert_eq!(
+            expand("{author} {path}", &document, 1),
+            "{author} OEBPS/ch1.xhtml"
+        );
+        assert_eq!(expand("{author:json}", &document, 1), "{author:json}");
+    }
+
+    #[test]
+    fn escapes_are_resolved() {
+        assert_eq!(unescape("a\\nb\\tc\\fd\\\\e"), "a\nb\tc\x0cd\\e");
+        assert_eq!(unescape("\\x"), "\\x");
+        assert_eq!(unescape("end\\"), "end\\");
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(preset("blank"), (None, None, "\n".into()));
+        assert_eq!(
+            preset("markers"),
+            (
+                Some("# begin OEBPS/ch1.xhtml\n".into()),
+                Some("# end OEBPS/ch1.xhtml\n".into()),
+                "\n".into()
+            )
+        );
+        assert_eq!(preset("form-feed"), (None, None, "\x0c\n".into()));
+        assert_eq!(
+            preset("yaml"),
+            (
+                Some(
+                    "---\ntitle: \"Loomings \\\"1\\\"\"\npath: \"OEBPS/ch1.xhtml\"\nindex: 1\n\
+                     size: 42\ncharset: UTF-8\nmtime: \"2024-02-29T12:00:00\"\n---\n"
+                        .into()
+                ),
+                None,
+                "\n".into()
+            )
+        );
+        assert_eq!(preset("none"), (None, None, String::new()));
+        assert!(Frame::new(&Options::new().set_preset("fancy")).is_err());
+    }
+
+    #[test]
+    fn templates_override_the_preset() {
+        let opts = Options::new()
+            .set_preset("markers")
+            .set_footer("")
+            .set_separator("\\n---\\n");
+        let frame = Frame::new(&opts).unwrap();
+        let document = document();
+        assert_eq!(
+            frame.header(&document, 2).as_deref(),
+            Some("# begin OEBPS/ch1.xhtml\n")
+        );
+        assert_eq!(frame.footer(&document, 2).as_deref(), Some(""));
+        assert_eq!(frame.separator(), "\n---\n");
+        // Stripped text has no blank line between the documents either.
+        let strip = Options::new().set_whitespace("strip");
+        assert_eq!(Frame::new(&strip).unwrap().separator(), "");
+        let markers = Frame::new(&strip.set_preset("markers")).unwrap();
+        assert_eq!(markers.separator(), "");
+        assert_eq!(Frame::new(&Options::new()).unwrap().separator(), "\n");
+    }
+}
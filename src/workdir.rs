@@ -0,0 +1,60 @@
+//! Private per-run working directory.
+
+use crate::MyError;
+use std::path::{Path, PathBuf};
+
+const PREFIX: &str = "rusty-html-extractor-";
+
+/// A uniquely named directory created under the system temp dir (`TMPDIR`)
+/// for the duration of a single run.
+///
+/// The directory is removed when the value is dropped, which also happens
+/// while unwinding from a panic, unless it was asked to be kept. A kept
+/// directory stays at [`WorkDir::path`].
+#[derive(Debug)]
+pub struct WorkDir {
+    dir: Option<tempfile::TempDir>,
+    path: PathBuf,
+    keep: bool,
+}
+
+impl WorkDir {
+    pub fn new(keep: bool) -> Result<Self, MyError> {
+        let dir = tempfile::Builder::new().prefix(PREFIX).tempdir()?;
+        Ok(Self {
+            path: dir.path().to_path_buf(),
+            dir: Some(dir),
+            keep,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Create a new uniquely named file inside the working directory.
+    pub fn file(&self, prefix: &str) -> Result<(std::fs::File, PathBuf), MyError> {
+        let (file, path) = tempfile::Builder::new()
+            .prefix(prefix)
+            .tempfile_in(&self.path)?
+            .keep()
+            .map_err(|e| e.error)?;
+        Ok((file, path))
+    }
+
+    /// Create an anonymous file inside the working directory, which is
+    /// removed as soon as it is closed.
+    pub fn spool(&self) -> Result<std::fs::File, MyError> {
+        Ok(tempfile::tempfile_in(&self.path)?)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if let Some(dir) = self.dir.take() {
+            if self.keep {
+                let _ = dir.keep();
+            }
+        }
+    }
+}